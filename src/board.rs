@@ -0,0 +1,237 @@
+//! Compact storage for the field: the cells are kept in the single row-major vector,
+//! and the sets of cells are bitboards with one bit per cell. `Cells` keeps the bitboard
+//! of each owner and the one of the other filled cells, they follow every `set`, so the floods
+//! and the scores don't rescan the field.
+//!
+//! `Cells` still can be read as `cells[i][j]`, the row is the slice of the vector,
+//! the cells are changed by `set` only.
+
+use crate::model::{Cell, Point};
+use std::ops::Index;
+use std::slice::Chunks;
+
+/// `m×n` matrix of cells, stored row by row
+#[derive(Clone, Debug)]
+pub struct Cells {
+    n: usize,
+    data: Vec<Cell>,
+    /// the `k`-th one is the set of the cells owned by the player `k`, grows as the owners appear
+    owners: Vec<BitBoard>,
+    /// the border, hidden and void cells
+    others: BitBoard,
+}
+
+/// the set of cells of `m×n` field, the cell `(i, j)` is the bit `i * n + j`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BitBoard {
+    m: usize,
+    n: usize,
+    words: Vec<u64>,
+}
+
+impl Cells {
+    pub fn new(m: usize, n: usize, cell: Cell) -> Cells {
+        Cells::from_data(n, vec![cell; m * n])
+    }
+
+    fn from_data(n: usize, data: Vec<Cell>) -> Cells {
+        let m = data.len().checked_div(n).unwrap_or(0);
+        let mut cells = Cells { n, data, owners: vec![], others: BitBoard::new(m, n) };
+        for k in 0..cells.data.len() {
+            cells.mark(k, cells.data[k], true);
+        }
+        cells
+    }
+
+    /// add the cell `k` to the board of `cell` or remove it from there
+    fn mark(&mut self, k: usize, cell: Cell, present: bool) {
+        let board = match cell {
+            Cell::Empty => return,
+            Cell::Owned(owner) => {
+                let owner = owner as usize;
+                if owner >= self.owners.len() {
+                    let (m, n) = (self.rows(), self.n);
+                    self.owners.resize(owner + 1, BitBoard::new(m, n));
+                }
+                &mut self.owners[owner]
+            }
+            Cell::Border | Cell::Hidden | Cell::Void => &mut self.others,
+        };
+        if present {
+            board.insert_index(k);
+        } else {
+            board.remove_index(k);
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.data.len().checked_div(self.n).unwrap_or(0)
+    }
+
+    pub fn cols(&self) -> usize {
+        self.n
+    }
+
+    pub fn get(&self, p: Point) -> Cell {
+        self.data[self.index_of(p)]
+    }
+
+    pub fn set(&mut self, p: Point, cell: Cell) {
+        let k = self.index_of(p);
+        self.mark(k, self.data[k], false);
+        self.mark(k, cell, true);
+        self.data[k] = cell;
+    }
+
+    /// the position of the cell in `as_slice()`
+    pub fn index_of(&self, p: Point) -> usize {
+        p.0 as usize * self.n + p.1 as usize
+    }
+
+    pub fn as_slice(&self) -> &[Cell] {
+        &self.data
+    }
+
+    /// iterate over the rows
+    pub fn iter(&self) -> Chunks<'_, Cell> {
+        self.data.chunks(self.n.max(1))
+    }
+
+    /// the cells matching the predicate
+    pub fn board(&self, predicate: impl Fn(Cell) -> bool) -> BitBoard {
+        let mut board = BitBoard::new(self.rows(), self.n);
+        for (k, &cell) in self.data.iter().enumerate() {
+            if predicate(cell) {
+                board.insert_index(k);
+            }
+        }
+        board
+    }
+
+    /// the cells owned by the player `owner`
+    pub fn owned(&self, owner: usize) -> BitBoard {
+        self.owners.get(owner).cloned().unwrap_or_else(|| BitBoard::new(self.rows(), self.n))
+    }
+
+    /// the number of the cells owned by the player `owner`
+    pub fn owned_count(&self, owner: usize) -> usize {
+        self.owners.get(owner).map(|board| board.len()).unwrap_or(0)
+    }
+
+    /// all the nonempty cells
+    pub fn filled(&self) -> BitBoard {
+        let mut board = self.others.clone();
+        for owned in &self.owners {
+            board.union_with(owned);
+        }
+        board
+    }
+}
+
+/// the boards follow the cells, so only the cells are compared
+impl PartialEq for Cells {
+    fn eq(&self, other: &Cells) -> bool {
+        self.n == other.n && self.data == other.data
+    }
+}
+
+impl Eq for Cells {}
+
+impl Index<usize> for Cells {
+    type Output = [Cell];
+
+    fn index(&self, i: usize) -> &[Cell] {
+        &self.data[i * self.n..(i + 1) * self.n]
+    }
+}
+
+impl<'a> IntoIterator for &'a Cells {
+    type Item = &'a [Cell];
+    type IntoIter = Chunks<'a, Cell>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl From<Vec<Vec<Cell>>> for Cells {
+    fn from(rows: Vec<Vec<Cell>>) -> Cells {
+        let n = rows.first().map(|row| row.len()).unwrap_or(0);
+        debug_assert!(rows.iter().all(|row| row.len() == n));
+        Cells::from_data(n, rows.into_iter().flatten().collect())
+    }
+}
+
+impl BitBoard {
+    pub fn new(m: usize, n: usize) -> BitBoard {
+        BitBoard { m, n, words: vec![0; (m * n).div_ceil(64)] }
+    }
+
+    pub fn from_points(m: usize, n: usize, points: &[Point]) -> BitBoard {
+        let mut board = BitBoard::new(m, n);
+        for &p in points {
+            board.insert(p);
+        }
+        board
+    }
+
+    /// false for the points outside of the field
+    pub fn contains(&self, p: Point) -> bool {
+        self.index_of(p).map(|k| self.contains_index(k)).unwrap_or(false)
+    }
+
+    pub fn insert(&mut self, p: Point) {
+        let k = self.index_of(p).expect("The point is outside of the board");
+        self.insert_index(k);
+    }
+
+    pub fn contains_index(&self, k: usize) -> bool {
+        self.words[k / 64] & (1 << (k % 64)) != 0
+    }
+
+    pub fn insert_index(&mut self, k: usize) {
+        self.words[k / 64] |= 1 << (k % 64);
+    }
+
+    pub fn remove_index(&mut self, k: usize) {
+        self.words[k / 64] &= !(1 << (k % 64));
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    pub fn union_with(&mut self, other: &BitBoard) {
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w |= *o;
+        }
+    }
+
+    /// the points of the set in the row-major order
+    pub fn points(&self) -> Vec<Point> {
+        let n = self.n;
+        let mut result = Vec::with_capacity(self.len());
+        for (w, &word) in self.words.iter().enumerate() {
+            let mut bits = word;
+            while bits != 0 {
+                let k = w * 64 + bits.trailing_zeros() as usize;
+                result.push(Point((k / n) as i16, (k % n) as i16));
+                bits &= bits - 1;
+            }
+        }
+        result
+    }
+
+    fn index_of(&self, p: Point) -> Option<usize> {
+        let Point(i, j) = p;
+        if 0 <= i && (i as usize) < self.m && 0 <= j && (j as usize) < self.n {
+            Some(i as usize * self.n + j as usize)
+        } else {
+            None
+        }
+    }
+}
@@ -1,62 +1,20 @@
-use crate::model::Move;
+use crate::board::BitBoard;
+use crate::bot::path_find::{find_nearest, FieldGrid};
+use crate::model::*;
 use crate::utils::Bound;
 use priority_queue::PriorityQueue;
-use core::fmt;
-use core::fmt::Write;
 use core::cmp::Ordering;
-use std::collections::HashMap;
-
-/// Decartes coordinates, (x, y)
-/// make our own coordinate system, in the name of René Descartes
-/// ^ y
-/// |
-/// |
-/// +-------> x
-#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd)]
-pub struct P(pub i16, pub i16);
-
-impl Ord for P {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.0 < other.0 {
-            Ordering::Less
-        } else if self.0 > other.0 {
-            Ordering::Greater
-        } else {
-            self.1.cmp(&other.1)
-        }
-    }
-}
-
-impl fmt::Debug for P {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_char('(')?;
-        fmt.write_str(&self.0.to_string())?;
-        fmt.write_char(',')?;
-        fmt.write_str(&self.1.to_string())?;
-        fmt.write_char(')')?;
-        Ok(())
-    }
-}
-
-impl fmt::Display for P {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_char('(')?;
-        fmt.write_str(&self.0.to_string())?;
-        fmt.write_char(',')?;
-        fmt.write_str(&self.1.to_string())?;
-        fmt.write_char(')')?;
-        Ok(())
-    }
-}
+use std::collections::{HashMap, VecDeque};
 
-pub fn distance(p: &P, q: &P) -> i32 {
-    ((p.0 - q.0).abs() as i32) + ((p.1 - q.1).abs() as i32)
+pub fn distance(p: &Point, q: &Point) -> i32 {
+    i32::from(p.manhattan(*q))
 }
 
-pub fn may_be_selected(base: P, arrow: P, cur: P) -> bool {
-    let P(xb, yb) = base;
-    let P(xa, ya) = arrow;
-    let P(xc, yc) = cur;
+pub fn may_be_selected(base: Point, arrow: Point, cur: Point) -> bool {
+    // in x to the right and y up
+    let (xb, yb) = (base.1, -base.0);
+    let (xa, ya) = (arrow.1, -arrow.0);
+    let (xc, yc) = (cur.1, -cur.0);
     // 4 3 2
     // 5 9 1
     // 6 7 8
@@ -73,60 +31,52 @@ pub fn may_be_selected(base: P, arrow: P, cur: P) -> bool {
     else                        { xa != xc && ya != yc }
 }
 
-pub fn direction(src: &P, dst: &P) -> Move {
-    let P(sx, sy) = src;
-    let P(dx, dy) = dst;
-    if dx == sx && dy <= sy {
-        Move::Down
-    } else if dx == sx && dy > sy {
-        Move::Up
-    } else if dx < sx {
-        Move::Left
-    } else {
-        Move::Right
-    }
+/// the move from `src` towards `dst`, along the row unless they are in the same column,
+/// `Down` if they are the same
+pub fn direction(src: &Point, dst: &Point) -> Move {
+    let dj = (dst.1 - src.1).signum();
+    let di = if dj == 0 { (dst.0 - src.0).signum() } else { 0 };
+    Move::from_delta(di, dj).filter(|&mv| mv != Move::Stop).unwrap_or(Move::Down)
 }
 
-pub fn build_path(src: &P, dst: &P, horz_first: bool) -> Vec<P> {
-    fn h(y: i16, a: i16, b: i16) -> Vec<P> {
-        if a < b { ((a + 1)..=b).map(|x| P(x, y)).collect() }
-            else if b < a { (b..a).map(|x| P(x, y)).rev().collect() }
+pub fn build_path(src: &Point, dst: &Point, horz_first: bool) -> Vec<Point> {
+    fn h(i: i16, a: i16, b: i16) -> Vec<Point> {
+        if a < b { ((a + 1)..=b).map(|j| Point(i, j)).collect() }
+            else if b < a { (b..a).map(|j| Point(i, j)).rev().collect() }
                 else { vec![] }
     }
-    fn v(x: i16, a: i16, b: i16) -> Vec<P> {
-        if a < b { ((a + 1)..=b).map(|y| P(x, y)).collect() }
-            else if b < a { (b..a).map(|y| P(x, y)).rev().collect() }
+    fn v(j: i16, a: i16, b: i16) -> Vec<Point> {
+        if a < b { ((a + 1)..=b).map(|i| Point(i, j)).collect() }
+            else if b < a { (b..a).map(|i| Point(i, j)).rev().collect() }
                 else { vec![] }
     }
-    let P(xs, ys) = src;
-    let P(xd, yd) = dst;
+    let Point(is, js) = *src;
+    let Point(id, jd) = *dst;
     let mut path = vec![];
     if horz_first {
         // do ← → then ↑ ↓
-        path.append(&mut h(*ys, *xs, *xd));
-        path.append(&mut v(*xd, *ys, *yd));
+        path.append(&mut h(is, js, jd));
+        path.append(&mut v(jd, is, id));
     } else {
         // do ↑ ↓ then ← →
-        path.append(&mut v(*xs, *ys, *yd));
-        path.append(&mut h(*yd, *xs, *xd));
+        path.append(&mut v(js, is, id));
+        path.append(&mut h(id, js, jd));
     };
     path
 }
 
-pub fn find_closest(m: i16, n: i16, src: &P, max: i16, predicate: impl Fn(&P) -> bool) -> Option<P> {
-    let P(xs, ys) = src;
-    let bounded = |p: &P| {
-        let P(x, y) = *p;
-        if 0 <= x && x < n && 0 <= y && y < m { *p }
-            else { P(x.bound(0, n - 1), y.bound(0, m - 1)) }
-    };
+/// the closest point satisfying the predicate in the growing rhombs around `src`,
+/// the points outside of the `m×n` field are moved to its edge
+pub fn find_closest(m: i16, n: i16, src: &Point, max: i16, predicate: impl Fn(&Point) -> bool) -> Option<Point> {
+    let Point(is, js) = *src;
+    let bounded = |p: &Point| Point(p.0.bound(0, m - 1), p.1.bound(0, n - 1));
     for r in 1..max {
         for k in 0..r {
             let ps = [
-                P(xs - k, ys + r - k),
-                P(xs - r + k, ys - k),
-                P(xs + k, ys - r + k),
-                P(xs + r - k, ys + k),
+                Point(is - r + k, js - k),
+                Point(is + k, js - r + k),
+                Point(is + r - k, js + k),
+                Point(is - k, js + r - k),
             ];
             let opt = ps.iter().map(bounded).find(&predicate);
             if opt.is_some() {
@@ -137,14 +87,14 @@ pub fn find_closest(m: i16, n: i16, src: &P, max: i16, predicate: impl Fn(&P) ->
     None
 }
 
-const NEIGHBORS: &[(i16, i16)] = &[(0, -1), (-1, 0), (0, 1), (1, 0)];
+const NEIGHBORS: [Move; 4] = [Move::Down, Move::Left, Move::Up, Move::Right];
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Weight {
     // h_score: f32, // heuristic distance from the end node
     pub f_score: i32, // g + h
     pub g_score: i32, // distance from the starting node
-    pub parent: P, // the point where we came from
+    pub parent: Point, // the point where we came from
 }
 
 impl PartialOrd for Weight {
@@ -161,13 +111,13 @@ impl Ord for Weight {
 }
 
 // for debug you can add
-pub fn a_star_find(src: &P, dst: &P,
-                   is_accessible: impl Fn(&P) -> bool,
-                   heuristic: impl Fn(&P, &P) -> i32,
-                   mut logger: Option<impl FnMut(&PriorityQueue<P, Weight>, &HashMap<P, P>) -> ()>,
-) -> Option<Vec<P>> {
-    let mut open_list: PriorityQueue<P, Weight> = PriorityQueue::new();
-    let mut closed_list: HashMap<P, P> = HashMap::new();
+pub fn a_star_find(src: &Point, dst: &Point,
+                   is_accessible: impl Fn(&Point) -> bool,
+                   heuristic: impl Fn(&Point, &Point) -> i32,
+                   mut logger: Option<impl FnMut(&PriorityQueue<Point, Weight>, &HashMap<Point, Point>) -> ()>,
+) -> Option<Vec<Point>> {
+    let mut open_list: PriorityQueue<Point, Weight> = PriorityQueue::new();
+    let mut closed_list: HashMap<Point, Point> = HashMap::new();
     // 1. Take the start node and put it on the open list
     open_list.push(*src, Weight { f_score: 0, g_score: 0, parent: *src});
     // 2. While there are nodes in the open list:
@@ -185,7 +135,7 @@ pub fn a_star_find(src: &P, dst: &P,
         //   b. Calculate `g` score (distance from starting node to this neighbor) and add it to the open list
         //   c. Calculate `f` score by adding heuristics to the `g` value.
         let accessible_neigh = NEIGHBORS.iter()
-            .map(|(dx, dy)| P(cur_p.0 + dx, cur_p.1 + dy))
+            .map(|&mv| cur_p.next(mv))
             .filter(|p| !closed_list.contains_key(&p) && is_accessible(&p));
         for np in accessible_neigh {
             // the neighbour could be already accessible from the different node
@@ -214,7 +164,7 @@ pub fn a_star_find(src: &P, dst: &P,
     None
 }
 
-pub fn backtrace(closed_list: &HashMap<P, P>, dst: P) -> Option<Vec<P>> {
+pub fn backtrace(closed_list: &HashMap<Point, Point>, dst: Point) -> Option<Vec<Point>> {
     let mut p = dst;
     let mut result = vec![p];
     while let Some(parent) = closed_list.get(&p) {
@@ -228,3 +178,266 @@ pub fn backtrace(closed_list: &HashMap<P, P>, dst: P) -> Option<Vec<P>> {
     result.reverse();
     Some(result)
 }
+
+/// the state for the search from the bot's view, the stats are zero, so the scores are
+/// what is captured since now, the hidden cells are considered to be empty,
+/// `origins` are where the players are respawned, usually their heads on `reset`
+pub fn simulation_state(gs: &GameStateView, origins: &[Point]) -> GameState {
+    let np = gs.players.len();
+    GameState {
+        field: unhidden_field(gs),
+        players: gs.players.clone(),
+        player_names: (0..np).map(player_name).collect(),
+        origins: (0..np).map(|k| origins.get(k).cloned().unwrap_or(Point(0, 0))).collect(),
+        stats: Stats {
+            iteration: 0,
+            filled_count: 0,
+            head_to_head_count: 0,
+            ouroboros_count: 0,
+            bite_count: 0,
+            scores: vec![0; np],
+        },
+        reordering: (0..np as u8).collect(),
+    }
+}
+
+/// the field of the view where the hidden cells are considered to be empty
+pub fn unhidden_field(gs: &GameStateView) -> Field {
+    let mut field = gs.field.clone();
+    for p in field.cells.board(|c| c == Cell::Hidden).points() {
+        field.cells.set(p, Cell::Empty);
+    }
+    field
+}
+
+/// the closed loop proposed by `plan_safe_loop`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PlannedLoop {
+    /// the cells to go through, the last one is the nonempty cell where the loop is closed
+    pub path: Vec<Point>,
+    /// the number of cells captured when the loop is closed
+    pub area: usize,
+    /// the number of moves to close the loop, it is `path.len()`
+    pub moves: usize,
+    /// the number of moves the closest enemy needs to reach the tail, `usize::MAX` if none can
+    pub enemy_moves: usize,
+}
+
+impl PlannedLoop {
+    /// the loop is closed before any enemy head can reach the tail
+    pub fn is_safe(&self) -> bool {
+        self.moves < self.enemy_moves
+    }
+
+    /// the move from `head` to the first cell of the path
+    pub fn next_move(&self, head: Point) -> Move {
+        self.path.first().and_then(|&p| head.direction_to(p)).unwrap_or(Move::Stop)
+    }
+}
+
+/// The candidate loops for the player `idx`: the rectangle-like paths going `a` cells in one
+/// direction, then `b` cells in the perpendicular one, `1 ≤ a ≤ max_side`, `0 ≤ b ≤ max_side`,
+/// and then returning home by the shortest way, if the tail is open, the straight return
+/// is a candidate too. The hidden cells are considered to be empty.
+pub fn candidate_loops(gs: &GameStateView, idx: usize, max_side: usize) -> Vec<PlannedLoop> {
+    let head = match gs.players[idx].head() {
+        Some(&head) => head,
+        None => return vec![],
+    };
+    let field = unhidden_field(gs);
+    // the tail exists only if the head is out of home
+    let body: &[Point] = if field.cells.get(head) == Cell::Empty { gs.players[idx].body() } else { &[] };
+    let heads: Vec<Point> = (0..gs.players.len())
+        .filter(|&k| k != idx)
+        .filter_map(|k| gs.players[k].head().cloned())
+        .collect();
+    let enemy = distance_map(&field, &heads, |p| is_passable(&field, p));
+
+    let mut result = vec![];
+    if !body.is_empty() {
+        result.extend(plan_loop(&field, body, head, &[], &enemy));
+    }
+    for &first in &Move::ALL {
+        let sides = match first {
+            Move::Right | Move::Left => [Move::Up, Move::Down],
+            _ => [Move::Left, Move::Right],
+        };
+        for (s, &second) in sides.iter().enumerate() {
+            for a in 1..=max_side {
+                // the straight paths are the same for both sides
+                for b in s..=max_side {
+                    result.extend(plan_loop(&field, body, head, &[(first, a), (second, b)], &enemy));
+                }
+            }
+        }
+    }
+    result
+}
+
+/// The best of the safe candidate loops, see `candidate_loops`, by the captured area
+/// per move, the shorter one if the rate is the same
+pub fn plan_safe_loop(gs: &GameStateView, idx: usize, max_side: usize) -> Option<PlannedLoop> {
+    let mut best: Option<PlannedLoop> = None;
+    for lp in candidate_loops(gs, idx, max_side).into_iter().filter(|lp| lp.is_safe()) {
+        let better = match &best {
+            None => true,
+            Some(b) => match (lp.area * b.moves).cmp(&(b.area * lp.moves)) {
+                Ordering::Greater => true,
+                Ordering::Equal => lp.moves < b.moves,
+                Ordering::Less => false,
+            },
+        };
+        if better {
+            best = Some(lp);
+        }
+    }
+    best
+}
+
+/// follow the `legs` from `head`, then return home by the shortest way,
+/// none if the path is blocked or bites the tail
+fn plan_loop(field: &Field, body: &[Point], head: Point, legs: &[(Move, usize)], enemy: &DistanceMap) -> Option<PlannedLoop> {
+    let mut blocked = BitBoard::from_points(field.m, field.n, body);
+    let mut path = vec![];
+    let mut cur = head;
+    let mut closed = false;
+    'legs: for &(mv, len) in legs {
+        for _ in 0..len {
+            let p = cur.next(mv);
+            if !p.is_inside(field.m, field.n) || field.cells.get(p) == Cell::Void || blocked.contains(p) {
+                return None;
+            }
+            path.push(p);
+            if field.cells.get(p) != Cell::Empty {
+                // the loop is closed earlier than planned
+                closed = true;
+                break 'legs;
+            }
+            blocked.insert(p);
+            cur = p;
+        }
+    }
+    if !closed {
+        path.append(&mut path_home(field, &blocked, cur)?);
+    }
+    let mut tail = body.to_vec();
+    tail.extend_from_slice(&path[..path.len() - 1]);
+    if tail.is_empty() {
+        return None;
+    }
+    let area = calculate_flood_area(field, &tail).len();
+    let enemy_moves = tail.iter().filter_map(|&p| enemy.get(p)).min().unwrap_or(usize::MAX);
+    Some(PlannedLoop { moves: path.len(), path, area, enemy_moves })
+}
+
+/// the shortest way from `from` over the empty cells not `blocked` to the nonempty cell
+fn path_home(field: &Field, blocked: &BitBoard, from: Point) -> Option<Vec<Point>> {
+    let grid = FieldGrid::new(field).with_blocked(blocked);
+    let is_home = |p: Point| p != from && field.cells.get(p) != Cell::Empty;
+    // the nonempty cells are never expanded, since they are the targets
+    let path = find_nearest(&grid, from, is_home, None)?;
+    Some(path.nodes[1..].to_vec())
+}
+
+/// the number of moves from the closest source to each cell
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DistanceMap {
+    m: usize,
+    n: usize,
+    distances: Vec<usize>,
+}
+
+impl DistanceMap {
+    /// none if the cell is outside or cannot be reached
+    pub fn get(&self, p: Point) -> Option<usize> {
+        if p.is_inside(self.m, self.n) {
+            Some(self.distances[p.0 as usize * self.n + p.1 as usize]).filter(|&d| d != usize::MAX)
+        } else {
+            None
+        }
+    }
+}
+
+/// the cells reached first by each player, see `voronoi`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Voronoi {
+    /// the distance to the closest head
+    pub distances: DistanceMap,
+    owners: Vec<Option<usize>>,
+}
+
+impl Voronoi {
+    /// the player reaching the cell strictly first, none if the cell is contested or unreachable
+    pub fn owner(&self, p: Point) -> Option<usize> {
+        self.distances.get(p).and_then(|_| self.owners[p.0 as usize * self.distances.n + p.1 as usize])
+    }
+
+    /// several players reach the cell at the same time
+    pub fn is_contested(&self, p: Point) -> bool {
+        self.distances.get(p).is_some() && self.owner(p).is_none()
+    }
+
+    /// the number of cells each of `np` players reaches first
+    pub fn sizes(&self, np: usize) -> Vec<usize> {
+        let mut sizes = vec![0; np];
+        for &k in self.owners.iter().flatten() {
+            if k < np {
+                sizes[k] += 1;
+            }
+        }
+        sizes
+    }
+}
+
+/// the usual rule for the moves: anything inside the field except the holes
+pub fn is_passable(field: &Field, p: Point) -> bool {
+    p.is_inside(field.m, field.n) && field.cells.get(p) != Cell::Void
+}
+
+/// BFS from all `sources` at once over the cells `passable` allows, the sources themselves
+/// are at the distance 0 whatever they are
+pub fn distance_map(field: &Field, sources: &[Point], passable: impl Fn(Point) -> bool) -> DistanceMap {
+    let heads: Vec<Option<Point>> = sources.iter().cloned().map(Some).collect();
+    voronoi(field, &heads, passable).distances
+}
+
+/// Multi-source BFS from the heads of the players, `heads[k]` is the head of the player `k`,
+/// none for the dead or hidden ones. The cell belongs to the player who reaches it first.
+pub fn voronoi(field: &Field, heads: &[Option<Point>], passable: impl Fn(Point) -> bool) -> Voronoi {
+    let (m, n) = (field.m, field.n);
+    let mut distances = vec![usize::MAX; m * n];
+    let mut owners: Vec<Option<usize>> = vec![None; m * n];
+    let mut queue = VecDeque::new();
+    for (k, &head) in heads.iter().enumerate() {
+        if let Some(h) = head.filter(|&h| h.is_inside(field.m, field.n)) {
+            let i = field.cells.index_of(h);
+            if distances[i] == usize::MAX {
+                distances[i] = 0;
+                owners[i] = Some(k);
+                queue.push_back(h);
+            } else if owners[i] != Some(k) {
+                owners[i] = None;
+            }
+        }
+    }
+    while let Some(cur) = queue.pop_front() {
+        let ci = field.cells.index_of(cur);
+        let (d, owner) = (distances[ci], owners[ci]);
+        for &mv in &Move::ALL {
+            let p = cur.next(mv);
+            if !p.is_inside(field.m, field.n) || !passable(p) {
+                continue;
+            }
+            let i = field.cells.index_of(p);
+            if distances[i] == usize::MAX {
+                distances[i] = d + 1;
+                owners[i] = owner;
+                queue.push_back(p);
+            } else if distances[i] == d + 1 && owners[i] != owner {
+                // reached at the same time from the different players
+                owners[i] = None;
+            }
+        }
+    }
+    Voronoi { distances: DistanceMap { m, n, distances }, owners }
+}
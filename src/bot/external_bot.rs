@@ -0,0 +1,235 @@
+//! The bot living in a separate process, so it can be written in any language.
+//!
+//! The engine talks to the child process through its stdin/stdout, one message per line.
+//! On `reset` the engine sends
+//! ```text
+//! reset <idx> <m> <n> <seed>
+//! <m lines of the field, as GameStateView::format_string>
+//! ```
+//! and expects nothing back. On `do_move` the engine sends
+//! ```text
+//! move <idx> <m> <n>
+//! <m lines of the field, as GameStateView::format_string>
+//! ```
+//! and expects a single line with one of the letters `r`, `u`, `l`, `d`, `s`
+//! (case insensitive). In the matches with the limited visibility the unseen cells are `?`,
+//! with more than 10 players the field is preceded by the line `encoding=wide`
+//! and the cells are 3 chars wide (see `GameState::format_string`).
+//! Anything else is recorded as a failure and the bot stays.
+//! If the answer doesn't come within the timeout the bot stays as well,
+//! and the late answer is skipped when it finally arrives.
+use crate::model::Bot;
+use crate::model::GameStateView;
+use crate::model::Move;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What went wrong with the child process
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum BotFailure {
+    /// the process could not be started
+    Spawn(String),
+    /// the process doesn't accept the input anymore
+    Crash(String),
+    /// the process closed its output
+    Eof,
+    /// the process answered with something that is not a move
+    Garbage(String),
+    /// the process didn't answer in time
+    Timeout,
+}
+
+#[derive(Debug)]
+pub struct ExternalBot {
+    program: String,
+    args: Vec<String>,
+    idx: usize,
+    timeout: Option<Duration>,
+    process: Option<Process>,
+    failures: Vec<BotFailure>,
+}
+
+/// the lines from stdout are read by the separate thread,
+/// `pending` is the number of answers we gave up waiting for
+#[derive(Debug)]
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    pending: usize,
+}
+
+impl ExternalBot {
+    pub fn new<S: AsRef<str>>(program: &str, args: &[S]) -> Self {
+        ExternalBot {
+            program: program.to_string(),
+            args: args.iter().map(|s| s.as_ref().to_string()).collect(),
+            idx: 0,
+            timeout: None,
+            process: None,
+            failures: vec![],
+        }
+    }
+
+    /// wait for the answer at most `timeout`, otherwise the move is `Stop`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// all the failures happened since the last `reset`
+    pub fn failures(&self) -> &[BotFailure] {
+        &self.failures
+    }
+
+    /// true if the child process is running and talking to us
+    pub fn is_alive(&self) -> bool {
+        self.process.is_some()
+    }
+
+    fn spawn(&mut self) {
+        self.kill();
+        let spawned = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn();
+        match spawned {
+            Ok(mut child) => {
+                // both handles are present, since we requested the pipes above
+                let stdin = child.stdin.take().expect("Broken invariant");
+                let stdout = BufReader::new(child.stdout.take().expect("Broken invariant"));
+                let (sender, lines) = channel();
+                // the thread finishes when the process closes stdout or we drop the receiver
+                thread::spawn(move || {
+                    for line in stdout.lines() {
+                        match line {
+                            Ok(line) => if sender.send(line).is_err() { break },
+                            Err(_) => break,
+                        }
+                    }
+                });
+                self.process = Some(Process { child, stdin, lines, pending: 0 });
+            }
+            Err(e) => self.failures.push(BotFailure::Spawn(e.to_string())),
+        }
+    }
+
+    fn kill(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            // the process may have already exited, that's fine
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+
+    fn send(&mut self, header: &str, gs: &GameStateView) -> bool {
+        let message = format!("{}\n{}\n", header, gs.format_string());
+        let result = match self.process {
+            Some(ref mut process) => process.stdin.write_all(message.as_bytes())
+                .and_then(|_| process.stdin.flush()),
+            None => return false,
+        };
+        if let Err(e) = result {
+            self.failures.push(BotFailure::Crash(e.to_string()));
+            self.kill();
+            return false;
+        }
+        true
+    }
+
+    fn receive(&mut self) -> Option<String> {
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let result = match self.process {
+            Some(ref mut process) => {
+                let mut result = receive_line(&process.lines, deadline);
+                // skip the late answers to the previous requests
+                while process.pending > 0 && result.is_ok() {
+                    process.pending -= 1;
+                    result = receive_line(&process.lines, deadline);
+                }
+                if result == Err(RecvTimeoutError::Timeout) {
+                    process.pending += 1;
+                }
+                result
+            }
+            None => return None,
+        };
+        match result {
+            Ok(line) => Some(line),
+            Err(RecvTimeoutError::Timeout) => {
+                self.failures.push(BotFailure::Timeout);
+                None
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                self.failures.push(BotFailure::Eof);
+                self.kill();
+                None
+            }
+        }
+    }
+}
+
+impl Bot for ExternalBot {
+    fn reset(&mut self, gs: &GameStateView, idx: usize, seed: u64) {
+        self.idx = idx;
+        self.failures.clear();
+        // every match starts with the fresh process
+        self.spawn();
+        let header = format!("reset {} {} {} {}", idx, gs.field.m, gs.field.n, seed);
+        self.send(&header, gs);
+    }
+
+    /// the own timeout doesn't exceed the match's one, so the late answer is skipped here
+    fn set_move_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(self.timeout.map_or(timeout, |t| t.min(timeout)));
+    }
+
+    fn do_move(&mut self, gs: &GameStateView) -> Move {
+        let header = format!("move {} {} {}", self.idx, gs.field.m, gs.field.n);
+        if !self.send(&header, gs) {
+            return Move::Stop;
+        }
+        match self.receive() {
+            Some(line) => match parse_move(&line) {
+                Some(mv) => mv,
+                None => {
+                    self.failures.push(BotFailure::Garbage(line.trim_end().to_string()));
+                    Move::Stop
+                }
+            },
+            None => Move::Stop,
+        }
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+fn receive_line(lines: &Receiver<String>, deadline: Option<Instant>) -> Result<String, RecvTimeoutError> {
+    match deadline {
+        Some(deadline) => {
+            let now = Instant::now();
+            let left = if deadline > now { deadline - now } else { Duration::from_millis(0) };
+            lines.recv_timeout(left)
+        }
+        None => lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    }
+}
+
+fn parse_move(line: &str) -> Option<Move> {
+    match line.trim() {
+        "r" | "R" => Some(Move::Right),
+        "u" | "U" => Some(Move::Up),
+        "l" | "L" => Some(Move::Left),
+        "d" | "D" => Some(Move::Down),
+        "s" | "S" => Some(Move::Stop),
+        _ => None,
+    }
+}
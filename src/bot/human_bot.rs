@@ -0,0 +1,79 @@
+use crate::model::*;
+use console::Key;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// The bot controlled by the arrow keys or WASD, the keys come from `utils::spawn_key_reader`.
+/// Each move waits for the whole tick, the last key pressed wins,
+/// if no key is pressed the bot keeps going in the last direction.
+#[derive(Debug)]
+pub struct HumanBot {
+    idx: usize,
+    keys: Receiver<Key>,
+    pace: Duration,
+    last_move: Move,
+}
+
+impl HumanBot {
+    pub fn new(idx: usize, keys: Receiver<Key>) -> Self {
+        HumanBot { idx, keys, pace: Duration::from_millis(300), last_move: Move::Stop }
+    }
+
+    /// how long a tick lasts, the human needs more than the bots
+    pub fn with_pace(mut self, pace: Duration) -> Self {
+        self.pace = pace;
+        self
+    }
+}
+
+/// the move for the arrow or WASD key, none for the other keys
+pub fn key_to_move(key: &Key) -> Option<Move> {
+    match key {
+        Key::ArrowUp | Key::Char('w') | Key::Char('W') => Some(Move::Up),
+        Key::ArrowLeft | Key::Char('a') | Key::Char('A') => Some(Move::Left),
+        Key::ArrowDown | Key::Char('s') | Key::Char('S') => Some(Move::Down),
+        Key::ArrowRight | Key::Char('d') | Key::Char('D') => Some(Move::Right),
+        _ => None,
+    }
+}
+
+impl Bot for HumanBot {
+    fn reset(&mut self, _gs: &GameStateView, idx: usize, _seed: u64) {
+        self.idx = idx;
+        self.last_move = Move::Stop;
+        // the keys pressed before the match don't count
+        while self.keys.try_recv().is_ok() {}
+    }
+
+    fn do_move(&mut self, gs: &GameStateView) -> Move {
+        let body = gs.players[self.idx].body();
+        let deadline = Instant::now() + self.pace;
+        let mut keys = vec![];
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            match self.keys.recv_timeout(deadline - now) {
+                Ok(key) => keys.push(key),
+                Err(RecvTimeoutError::Timeout) => break,
+                // nobody is at the keyboard, just keep going
+                Err(RecvTimeoutError::Disconnected) => {
+                    std::thread::sleep(deadline - now);
+                    break;
+                }
+            }
+        }
+        if body.is_empty() {
+            self.last_move = Move::Stop;
+            return Move::Stop;
+        }
+        // turning back bites the own tail, it's a slip of the finger rather than the intent
+        let head = body[body.len() - 1];
+        let back = if body.len() > 1 { Some(body[body.len() - 2]) } else { None };
+        if let Some(mv) = keys.iter().rev().filter_map(key_to_move).find(|&mv| Some(head.next(mv)) != back) {
+            self.last_move = mv;
+        }
+        self.last_move
+    }
+}
@@ -4,7 +4,6 @@ use crate::bot::common::direction;
 use crate::bot::common::distance;
 use crate::bot::common::find_closest;
 use crate::bot::common::may_be_selected;
-use crate::bot::common::P;
 use crate::bot::common::Weight;
 use crate::model::Bot;
 use crate::model::Cell;
@@ -26,20 +25,20 @@ pub struct KillerBot {
     random: Rc<RefCell<IsaacRng>>,
     m: usize,
     n: usize,
-    cur_me: Vec<P>,
-    last_me: Vec<P>,
-    path: Vec<P>,
+    cur_me: Vec<Point>,
+    last_me: Vec<Point>,
+    path: Vec<Point>,
     path_idx: usize,
     stay_count: i32,
-    all: Vec<Vec<P>>,
+    all: Vec<Vec<Point>>,
     chasing: bool, // if the bot is trying to bite someone now
 }
 
 struct KillerBotAlg<'a> {
     idx: usize,
     gs: &'a GameStateView,
-    cur_me: &'a Vec<P>,
-    all: &'a Vec<Vec<P>>,
+    cur_me: &'a Vec<Point>,
+    all: &'a Vec<Vec<Point>>,
     random: Rc<RefCell<IsaacRng>>,
 }
 
@@ -146,7 +145,7 @@ impl Bot for KillerBot {
             if let Some(the_empty) = empties[..cmp::min(4, empties.len())].last() {
                 let the_direction = direction(cur_head, the_empty);
                 let mut path = build_path(cur_head, the_empty, the_direction == Move::Left || the_direction == Move::Right);
-                let filtering_fun = |p: &P| alg.border_or_owned_partial(*cur_head, *the_empty, *p);
+                let filtering_fun = |p: &Point| alg.border_or_owned_partial(*cur_head, *the_empty, *p);
                 if let Some(border) = alg.find_closest_on_field(the_empty, filtering_fun) {
                     let horz_first = self.random.borrow_mut().gen();
                     let mut appendix = build_path(the_empty, &border, horz_first);
@@ -172,20 +171,21 @@ impl Bot for KillerBot {
 }
 
 impl<'a> KillerBotAlg<'a> {
-    fn find_closest_on_field(&self, src: &P, predicate: impl Fn(&P) -> bool) -> Option<P> {
+    fn find_closest_on_field(&self, src: &Point, predicate: impl Fn(&Point) -> bool) -> Option<Point> {
         let m = self.gs.field.m as i16;
         let n = self.gs.field.n as i16;
         find_closest(m, n, src, m + n, predicate)
     }
 
-    fn find_random(&self, attempts: usize, predicate: impl Fn(&P) -> bool) -> Vec<P> {
+    fn find_random(&self, attempts: usize, predicate: impl Fn(&Point) -> bool) -> Vec<Point> {
         let m = self.gs.field.m as i16;
         let n = self.gs.field.n as i16;
-        let mut buf: Vec<P> = Vec::with_capacity(attempts);
+        let mut buf: Vec<Point> = Vec::with_capacity(attempts);
         for _ in 0..attempts {
-            let x = self.random.borrow_mut().gen_range(0, n as i16);
-            let y = self.random.borrow_mut().gen_range(0, m as i16);
-            let p = P(x, y);
+            let j = self.random.borrow_mut().gen_range(0, n as i16);
+            // the row is counted from the bottom
+            let i = m - 1 - self.random.borrow_mut().gen_range(0, m as i16);
+            let p = Point(i, j);
             if predicate(&p) {
                 buf.push(p)
             }
@@ -194,31 +194,30 @@ impl<'a> KillerBotAlg<'a> {
     }
 
     /// find a
-    fn find_safe_path(&self, src: &P, dst: &P) -> Option<Vec<P>> {
-        let m = self.gs.field.m as i16;
-        let n = self.gs.field.n as i16;
-        let is_boundary = |p: &P| {
-            let P(x, y) = *p;
-            0 <= y && y < m && 0 <= x && x < n && !self.cur_me.contains(&p)
+    fn find_safe_path(&self, src: &Point, dst: &Point) -> Option<Vec<Point>> {
+        let m = self.gs.field.m;
+        let n = self.gs.field.n;
+        let is_boundary = |p: &Point| {
+            p.is_inside(m, n) && !self.cur_me.contains(&p) && self.cells(p) != Cell::Void
         };
-        let heuristic = |p: &P, q: &P| distance(p, q);
-        let logger: Option<fn(&PriorityQueue<P, Weight>, &HashMap<P, P>)> = None;
+        let heuristic = |p: &Point, q: &Point| distance(p, q);
+        let logger: Option<fn(&PriorityQueue<Point, Weight>, &HashMap<Point, Point>)> = None;
         a_star_find(&src, &dst, is_boundary, heuristic, logger)
     }
 
-    fn find_random_empty(&self, attempts: usize) -> Vec<P> {
+    fn find_random_empty(&self, attempts: usize) -> Vec<Point> {
         self.find_random(attempts, |p| self.cells(&p) == Cell::Empty)
     }
 
     /// to close the path we are interested in not any border or owned,
     /// but we need to find such cell, direction to that will not cross our body
-    fn border_or_owned_partial(&self, o: P, a: P, c: P) -> bool {
+    fn border_or_owned_partial(&self, o: Point, a: Point, c: Point) -> bool {
         let cell = self.cells(&c);
-        (cell != Cell::Empty) && may_be_selected(o, a, c)
+        (cell != Cell::Empty && cell != Cell::Hidden && cell != Cell::Void) && may_be_selected(o, a, c)
     }
 
-    fn find_enemy_nearby(&self, cur_head: &P, radius: i16) -> Option<P> {
-        let mut enemy: Option<P> = None;
+    fn find_enemy_nearby(&self, cur_head: &Point, radius: i16) -> Option<Point> {
+        let mut enemy: Option<Point> = None;
         let np = self.gs.players.len();
         let m = self.gs.field.m as i16;
         let n = self.gs.field.n as i16;
@@ -238,26 +237,13 @@ impl<'a> KillerBotAlg<'a> {
 
     // === helpers ===
 
-    fn cells(&self, p: &P) -> Cell {
-        let m = self.gs.field.m;
-        let from_decartes_x = |x: i16| x as usize;
-        let from_decartes_y = |y: i16| m - 1 - (y as usize);
-        self.gs.field.cells[from_decartes_y(p.1)][from_decartes_x(p.0)]
+    fn cells(&self, p: &Point) -> Cell {
+        self.gs.field.cells.get(*p)
     }
 }
 
-fn to_decartes(gs: &GameStateView, p: &Point) -> P {
-    let m = gs.field.m as i16;
-    // let n = gs.field.n as i16;
-    P(p.1, m - 1 - p.0)
-}
-
 /// the head is the _last_ element, similar to `self.gs.players[idx]`
-fn player_bodies(gs: &GameStateView, idx: usize) -> (Vec<P>, Vec<Vec<P>>) {
-    let me = gs.players[idx].body().iter().map(|p| to_decartes(gs, p)).collect();
-    let mut all: Vec<Vec<P>> = vec![];
-    for k in 0..gs.players.len() {
-        all.push(gs.players[k].body().iter().map(|p| to_decartes(gs, p)).collect())
-    }
-    (me, all)
+fn player_bodies(gs: &GameStateView, idx: usize) -> (Vec<Point>, Vec<Vec<Point>>) {
+    let all: Vec<Vec<Point>> = gs.players.iter().map(|p| p.body().clone()).collect();
+    (all[idx].clone(), all)
 }
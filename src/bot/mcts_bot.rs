@@ -0,0 +1,223 @@
+//! Monte Carlo Tree Search over the moves of the bot.
+//!
+//! The tree is open loop: the node is the sequence of the bot's moves, while the opponents
+//! make the random moves in each simulation, so the same node is reached in different states.
+//! The moves are applied by `model::step`, first the bot, then the others in the cyclic order.
+//! After the new node is added the simulation continues with the random moves of everyone,
+//! the reward is the captured area of the bot minus the best of the opponents.
+use crate::bot::common::simulation_state;
+use crate::model::*;
+use rand::IsaacRng;
+use rand::SeedableRng;
+use rand::prelude::{FromEntropy, Rng};
+use std::time::{Duration, Instant};
+
+/// the exploration constant of UCB1
+const EXPLORATION: f64 = 1.4;
+
+/// How long the bot thinks about a move
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Budget {
+    /// the fixed number of simulations, the moves are reproducible with the same seed
+    Iterations(u32),
+    /// simulate until the time is over, at least one simulation is made
+    Time(Duration),
+}
+
+#[derive(Clone, Debug)]
+pub struct MctsBot {
+    idx: usize,
+    random: IsaacRng,
+    budget: Budget,
+    depth: u32,
+    origins: Vec<Point>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Node {
+    visits: u32,
+    reward: f64,
+    children: [Option<usize>; 4],
+}
+
+impl MctsBot {
+    pub fn new(idx: usize, budget: Budget) -> Self {
+        MctsBot {
+            idx,
+            random: IsaacRng::from_entropy(),
+            budget,
+            depth: 24,
+            origins: vec![],
+        }
+    }
+
+    /// the number of ticks in a single simulation, including the ones in the tree
+    pub fn with_depth(mut self, depth: u32) -> Self {
+        self.depth = depth.max(1);
+        self
+    }
+
+    fn simulate(&mut self, root: &GameState, tree: &mut Vec<Node>) {
+        let np = root.players.len();
+        let mut gs = root.clone();
+        let mut last_moves = vec![Move::Stop; np];
+        let mut path = vec![0];
+        let mut penalty = 0.0;
+        let mut in_tree = true;
+        for _ in 0..self.depth {
+            // the bot may have nowhere to respawn on the partly hidden field, the rollout ends then
+            if gs.players[self.idx].body().is_empty() {
+                break;
+            }
+            let mv = if in_tree {
+                let node = *path.last().unwrap();
+                let (k, expanded) = self.select(tree, node);
+                let child = match tree[node].children[k] {
+                    Some(child) => child,
+                    None => {
+                        tree.push(Node::default());
+                        tree[node].children[k] = Some(tree.len() - 1);
+                        tree.len() - 1
+                    }
+                };
+                path.push(child);
+                in_tree = !expanded;
+                Move::ALL[k]
+            } else {
+                rollout_move(&gs, self.idx, last_moves[self.idx], &mut self.random)
+            };
+            last_moves[self.idx] = mv;
+            penalty += tick(&mut gs, self.idx, mv, &mut last_moves, &mut self.random);
+        }
+        // the loop made in `depth` ticks captures at most about `(depth / 4)²` cells
+        let scale = f64::from(self.depth * self.depth) / 16.0;
+        let mine = f64::from(gs.stats.scores[self.idx]);
+        let best_other = (0..np).filter(|&k| k != self.idx)
+            .map(|k| f64::from(gs.stats.scores[k]))
+            .fold(0.0, f64::max);
+        let reward = (mine - best_other - penalty) / scale;
+        for node in path {
+            tree[node].visits += 1;
+            tree[node].reward += reward;
+        }
+    }
+
+    /// the child by UCB1, the untried moves go first in the random order,
+    /// returns the move index and true if the child is new
+    fn select(&mut self, tree: &[Node], node: usize) -> (usize, bool) {
+        let untried: Vec<usize> = (0..Move::ALL.len()).filter(|&k| tree[node].children[k].is_none()).collect();
+        if !untried.is_empty() {
+            return (untried[self.random.gen_range(0, untried.len())], true);
+        }
+        let ln = f64::from(tree[node].visits.max(1)).ln();
+        let ucb = |k: usize| {
+            let child = &tree[tree[node].children[k].unwrap()];
+            let visits = f64::from(child.visits.max(1));
+            child.reward / visits + EXPLORATION * (ln / visits).sqrt()
+        };
+        let best = (0..Move::ALL.len())
+            .max_by(|&a, &b| ucb(a).partial_cmp(&ucb(b)).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap();
+        (best, false)
+    }
+}
+
+impl Bot for MctsBot {
+    fn reset(&mut self, gs: &GameStateView, idx: usize, seed: u64) {
+        self.idx = idx;
+        self.random = IsaacRng::seed_from_u64(seed);
+        // the players start at their origins, they are respawned there
+        self.origins = gs.players.iter().map(|p| p.head().cloned().unwrap_or(Point(0, 0))).collect();
+    }
+
+    fn do_move(&mut self, gs: &GameStateView) -> Move {
+        if gs.players[self.idx].body().is_empty() {
+            return Move::Stop;
+        }
+        let root = simulation_state(gs, &self.origins);
+        let mut tree = vec![Node::default()];
+        match self.budget {
+            Budget::Iterations(count) => {
+                for _ in 0..count.max(1) {
+                    self.simulate(&root, &mut tree);
+                }
+            }
+            Budget::Time(limit) => {
+                let started = Instant::now();
+                loop {
+                    self.simulate(&root, &mut tree);
+                    if started.elapsed() >= limit {
+                        break;
+                    }
+                }
+            }
+        }
+        // the most visited move is the most robust one
+        let children = tree[0].children;
+        (0..Move::ALL.len())
+            .filter_map(|k| children[k].map(|c| (k, tree[c].visits)))
+            .max_by_key(|&(_, visits)| visits)
+            .map(|(k, _)| Move::ALL[k])
+            .unwrap_or(Move::Stop)
+    }
+}
+
+/// the bot `idx` moves, then the others make the random moves,
+/// returns the penalty for the tail the bot `idx` has lost
+fn tick(gs: &mut GameState, idx: usize, mv: Move, last_moves: &mut [Move], random: &mut IsaacRng) -> f64 {
+    let np = gs.players.len();
+    let mut penalty = 0.0;
+    for k in (0..np).map(|k| (idx + k) % np) {
+        // the opponents hidden by the visibility just stay
+        if gs.players[k].body().is_empty() {
+            continue;
+        }
+        let mv = if k == idx { mv } else { rollout_move(gs, k, last_moves[k], random) };
+        last_moves[k] = mv;
+        let length = gs.players[idx].body().len();
+        let ouroboros = gs.stats.ouroboros_count;
+        let bites = gs.stats.bite_count;
+        step(gs, k, mv);
+        let died = (k == idx && gs.stats.ouroboros_count > ouroboros)
+            || (k != idx && gs.stats.bite_count > bites && gs.players[idx].body().len() < length);
+        if died {
+            penalty += length as f64;
+        }
+    }
+    penalty
+}
+
+/// the move in `RandomBot` manner: keep going the same way mostly,
+/// turn randomly sometimes, don't bite the own tail; the longer the tail is,
+/// the more likely the player turns back home to close the loop
+fn rollout_move(gs: &GameState, k: usize, last: Move, random: &mut IsaacRng) -> Move {
+    let body = gs.players[k].body();
+    let head = match body.last() {
+        Some(&head) => head,
+        None => return Move::Stop,
+    };
+    let field = &gs.field;
+    let is_free = |p: Point| {
+        p.is_inside(field.m, field.n)
+            && field.cells[p.0 as usize][p.1 as usize] != Cell::Void
+            && !body.contains(&p)
+    };
+    let safe: Vec<(Move, Point)> = Move::ALL.iter()
+        .map(|&mv| (mv, head.next(mv)))
+        .filter(|&(_, p)| is_free(p))
+        .collect();
+    if safe.is_empty() {
+        return Move::Stop;
+    }
+    if random.gen_range(0, 16) < body.len() {
+        let is_home = |p: Point| is_free(p) && field.cells[p.0 as usize][p.1 as usize] != Cell::Empty;
+        if let Some(home) = find_nearest_rhomb(field, head, is_home) {
+            let distance = |p: Point| p.manhattan(home);
+            return safe.iter().min_by_key(|&&(_, p)| distance(p)).map(|&(mv, _)| mv).unwrap();
+        }
+    }
+    if safe.iter().any(|&(mv, _)| mv == last) && random.gen_range(0, 4) != 0 {
+        return last;
+    }
+    safe[random.gen_range(0, safe.len())].0
+}
@@ -1,10 +1,19 @@
-
 pub mod random_bot;
 pub mod killer_bot;
 pub mod test_bot;
+pub mod external_bot;
+pub mod mcts_bot;
+pub mod paranoid_bot;
+pub mod human_bot;
 pub mod common;
+pub mod path_find;
 
 pub use crate::bot::random_bot::*;
 pub use crate::bot::killer_bot::*;
 pub use crate::bot::test_bot::*;
+pub use crate::bot::external_bot::*;
+pub use crate::bot::mcts_bot::*;
+pub use crate::bot::paranoid_bot::*;
+pub use crate::bot::human_bot::*;
 pub use crate::bot::common::*;
+pub use crate::bot::path_find::*;
@@ -0,0 +1,231 @@
+//! Depth-limited paranoid search: all the opponents are considered to be a single adversary,
+//! that tries to minimize the evaluation of the bot, so the search is minimax with alpha-beta
+//! pruning, where the bot's ply is followed by the plies of the opponents.
+//!
+//! Only the opponents close enough to matter take part in the search, the others stay.
+//! The moves are made and unmade with `step_with_undo` and `unstep` on the single state.
+use crate::bot::common::simulation_state;
+use crate::model::*;
+
+
+/// the weights of the evaluation terms
+const AREA_WEIGHT: i32 = 16;
+const LOST_WEIGHT: i32 = 16;
+const EXPOSED_WEIGHT: i32 = 16;
+const RETURN_WEIGHT: i32 = 4;
+const TAIL_WEIGHT: i32 = 1;
+const POTENTIAL_WEIGHT: i32 = 4;
+/// how many extra moves the enemy needs to reach the tail, so that the tail is safe
+const SAFETY_MARGIN: i16 = 2;
+
+#[derive(Clone, Debug)]
+pub struct ParanoidBot {
+    idx: usize,
+    depth: usize,
+    adversaries: usize,
+    origins: Vec<Point>,
+    last_move: Move,
+}
+
+impl ParanoidBot {
+    /// `depth` is the number of rounds to look ahead, a round is the move of the bot
+    /// and the moves of the adversaries
+    pub fn new(idx: usize, depth: usize) -> Self {
+        ParanoidBot {
+            idx,
+            depth: depth.max(1),
+            adversaries: 2,
+            origins: vec![],
+            last_move: Move::Stop,
+        }
+    }
+
+    /// the maximal number of the closest opponents to take into account
+    pub fn with_adversaries(mut self, adversaries: usize) -> Self {
+        self.adversaries = adversaries;
+        self
+    }
+
+    /// the bot goes first, then the closest opponents within the reach of the search
+    fn turn_order(&self, gs: &GameState) -> Vec<usize> {
+        let me = gs.players[self.idx].body();
+        let reach = 2 * self.depth as i16 + 1;
+        let mut others: Vec<(i16, usize)> = (0..gs.players.len())
+            .filter(|&k| k != self.idx)
+            .filter_map(|k| gs.players[k].head().map(|&h| (k, h)))
+            .map(|(k, h)| (me.iter().map(|&p| p.manhattan(h)).min().unwrap_or(i16::MAX), k))
+            .filter(|&(d, _)| d <= reach)
+            .collect();
+        others.sort();
+        let mut order = vec![self.idx];
+        order.extend(others.into_iter().take(self.adversaries).map(|(_, k)| k));
+        order
+    }
+}
+
+impl Bot for ParanoidBot {
+    fn reset(&mut self, gs: &GameStateView, idx: usize, _seed: u64) {
+        self.idx = idx;
+        self.last_move = Move::Stop;
+        // the players start at their origins, they are respawned there
+        self.origins = gs.players.iter().map(|p| p.head().cloned().unwrap_or(Point(0, 0))).collect();
+    }
+
+    fn do_move(&mut self, gs: &GameStateView) -> Move {
+        if gs.players[self.idx].body().is_empty() {
+            return Move::Stop;
+        }
+        let mut state = simulation_state(gs, &self.origins);
+        let order = self.turn_order(&state);
+        let search = Search { idx: self.idx, order: &order, plies: self.depth * order.len() };
+        // try the last move first, it's often the best one and gives more cutoffs
+        let mut moves = candidate_moves(&state, self.idx);
+        if let Some(pos) = moves.iter().position(|&mv| mv == self.last_move) {
+            moves[..=pos].rotate_right(1);
+        }
+        let mut best = (i32::MIN, Move::Stop);
+        for mv in moves {
+            let undo = step_with_undo(&mut state, self.idx, mv);
+            let cost = step_cost(&state, &undo, self.idx, self.idx);
+            let value = search.run(&mut state, 1, best.0, i32::MAX, cost);
+            unstep(&mut state, undo);
+            if value > best.0 {
+                best = (value, mv);
+            }
+        }
+        self.last_move = best.1;
+        best.1
+    }
+}
+
+struct Search<'a> {
+    idx: usize,
+    order: &'a [usize],
+    plies: usize,
+}
+
+impl<'a> Search<'a> {
+    /// the value of the state for the bot, `cost` is what the bot has paid on the way here
+    fn run(&self, gs: &mut GameState, ply: usize, mut alpha: i32, mut beta: i32, cost: i32) -> i32 {
+        // the bot may have nowhere to respawn on the partly hidden field, the search ends then
+        if ply == self.plies || gs.players[self.idx].body().is_empty() {
+            return evaluate(gs, self.idx, cost);
+        }
+        let k = self.order[ply % self.order.len()];
+        let maximizing = k == self.idx;
+        let moves = candidate_moves(gs, k);
+        if moves.is_empty() {
+            return self.run(gs, ply + 1, alpha, beta, cost);
+        }
+        let mut best = if maximizing { i32::MIN } else { i32::MAX };
+        for mv in moves {
+            let undo = step_with_undo(gs, k, mv);
+            let cost = cost + step_cost(gs, &undo, k, self.idx);
+            let value = self.run(gs, ply + 1, alpha, beta, cost);
+            unstep(gs, undo);
+            if maximizing {
+                best = best.max(value);
+                alpha = alpha.max(value);
+            } else {
+                best = best.min(value);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+/// the moves that change the position and don't bite the own tail,
+/// `Stop` is the last resort when there are no such moves
+fn candidate_moves(gs: &GameState, k: usize) -> Vec<Move> {
+    let body = gs.players[k].body();
+    let head = match body.last() {
+        Some(&head) => head,
+        None => return vec![],
+    };
+    let field = &gs.field;
+    let moves: Vec<Move> = Move::ALL.iter().cloned().filter(|&mv| {
+        let p = head.next(mv);
+        p.is_inside(field.m, field.n) && field.cells[p.0 as usize][p.1 as usize] != Cell::Void && !body.contains(&p)
+    }).collect();
+    if moves.is_empty() { vec![Move::Stop] } else { moves }
+}
+
+/// the price of the step of the player `k` for the bot `idx`: the tail lost,
+/// and each tick with the open tail, the longer the tail is, the more the bot risks,
+/// so the bot closes the loop as soon as it's worth it instead of postponing it
+fn step_cost(gs: &GameState, undo: &Undo, k: usize, idx: usize) -> i32 {
+    let mut cost = LOST_WEIGHT * lost_tail(gs, undo, k, idx);
+    if k == idx {
+        cost += TAIL_WEIGHT * (gs.players[idx].body().len() as i32 - 1);
+    }
+    cost
+}
+
+/// how much of the tail the bot `idx` has lost with the step of the player `k` just made:
+/// the bot is bitten, its tail is captured or it bites itself,
+/// when the bot closes the loop its body is replaced too, but that's not a loss
+fn lost_tail(gs: &GameState, undo: &Undo, k: usize, idx: usize) -> i32 {
+    let old = undo.bodies.iter().filter_map(|change| match change {
+        BodyChange::Replaced(j, old) if *j == idx => Some(old.len() as i32),
+        _ => None,
+    }).next();
+    match old {
+        Some(len) if k != idx => (len - gs.players[idx].body().len() as i32).max(0),
+        Some(len) if gs.stats.ouroboros_count > undo.ouroboros_count => len - 1,
+        _ => 0,
+    }
+}
+
+/// the evaluation of the state for the bot `idx`:
+/// - the area captured by the bot minus the best of the opponents
+/// - the cost of the way, see `step_cost`
+/// - the tail exposure, if an enemy head is closer to the tail than the bot to home
+/// - the distance to return home, the longer it is, the more risky the loop is
+fn evaluate(gs: &GameState, idx: usize, cost: i32) -> i32 {
+    let scores = &gs.stats.scores;
+    let best_other = (0..scores.len()).filter(|&k| k != idx).map(|k| i32::from(scores[k])).max().unwrap_or(0);
+    let mut value = AREA_WEIGHT * (i32::from(scores[idx]) - best_other) - cost;
+    let body = gs.players[idx].body();
+    if body.len() > 1 {
+        let field = &gs.field;
+        let head = *body.last().unwrap();
+        // the loop is closed on any nonempty cell, not only on the own ones
+        let is_home = |p: Point| p.is_inside(field.m, field.n) && match field.cells[p.0 as usize][p.1 as usize] {
+            Cell::Border | Cell::Owned(_) => !body.contains(&p),
+            _ => false,
+        };
+        let home = find_nearest_rhomb(field, head, is_home)
+            .map(|p| head.manhattan(p))
+            .unwrap_or((field.m + field.n) as i16);
+        let enemy = (0..gs.players.len())
+            .filter(|&k| k != idx)
+            .filter_map(|k| gs.players[k].head())
+            .flat_map(|&h| body[..body.len() - 1].iter().map(move |&p| h.manhattan(p)))
+            .min()
+            .unwrap_or(i16::MAX);
+        // the loop is worth something even before it's closed, it's what is captured
+        // if the bot returns home right now
+        let potential = calculate_flood_area(field, body).len() as i32;
+        value -= RETURN_WEIGHT * i32::from(home) * body.len() as i32;
+        if enemy <= home + SAFETY_MARGIN {
+            // the tail is likely to be bitten before the loop is closed
+            value -= EXPOSED_WEIGHT * body.len() as i32;
+        } else {
+            value += POTENTIAL_WEIGHT * potential;
+        }
+    } else if let Some(&head) = body.last() {
+        // go out to capture something
+        let field = &gs.field;
+        let is_empty = |p: Point| p.is_inside(field.m, field.n) && field.cells[p.0 as usize][p.1 as usize] == Cell::Empty;
+        // the cell under the head doesn't count
+        let empty = find_nearest_rhomb(field, head, |p| p != head && is_empty(p))
+            .map(|p| head.manhattan(p))
+            .unwrap_or(0);
+        value -= i32::from(empty);
+    }
+    value
+}
@@ -0,0 +1,149 @@
+//! A* over any graph implementing `Grid`.
+//!
+//! The steps have the costs, so the bot can prefer walking over its own cells to extending
+//! the tail over the empty ones. The ties are broken deterministically: by `f`, then by the
+//! heuristic, so the node closer to the target goes first, then by the order of the nodes.
+//! The search stops after `max_expansions` nodes are taken from the open list, if given.
+
+use crate::board::BitBoard;
+use crate::model::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+/// The graph to search the path in.
+pub trait Grid {
+    type Node: Copy + Eq + Hash + Ord;
+
+    /// push to `out` the nodes reachable from `node` in one step and the costs of the steps,
+    /// the cost must be at least 1
+    fn neighbors(&self, node: Self::Node, out: &mut Vec<(Self::Node, u32)>);
+
+    /// the lower bound of the cost from `node` to `target`
+    fn heuristic(&self, node: Self::Node, target: Self::Node) -> u32;
+}
+
+/// the path found, `nodes` start with the source and end with the target
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Path<N> {
+    pub nodes: Vec<N>,
+    pub cost: u32,
+    /// the number of nodes taken from the open list
+    pub expanded: usize,
+}
+
+/// The cells of the field, the moves are to the adjacent cells except `Void`
+/// and the `blocked` ones, entering the empty cell costs `empty_cost`, any other costs 1.
+#[derive(Clone, Debug)]
+pub struct FieldGrid<'a> {
+    field: &'a Field,
+    blocked: Option<&'a BitBoard>,
+    empty_cost: u32,
+}
+
+impl<'a> FieldGrid<'a> {
+    pub fn new(field: &'a Field) -> Self {
+        FieldGrid { field, blocked: None, empty_cost: 1 }
+    }
+
+    /// the cells not to enter, e.g. the own tail
+    pub fn with_blocked(mut self, blocked: &'a BitBoard) -> Self {
+        self.blocked = Some(blocked);
+        self
+    }
+
+    /// the price of the step extending the tail
+    pub fn with_empty_cost(mut self, cost: u32) -> Self {
+        self.empty_cost = cost.max(1);
+        self
+    }
+}
+
+impl<'a> Grid for FieldGrid<'a> {
+    type Node = Point;
+
+    fn neighbors(&self, node: Point, out: &mut Vec<(Point, u32)>) {
+        let field = self.field;
+        for p in node.neighbors() {
+            if !p.is_inside(field.m, field.n) || self.blocked.map(|b| b.contains(p)).unwrap_or(false) {
+                continue;
+            }
+            match field.cells.get(p) {
+                Cell::Void => {}
+                Cell::Empty => out.push((p, self.empty_cost)),
+                _ => out.push((p, 1)),
+            }
+        }
+    }
+
+    fn heuristic(&self, node: Point, target: Point) -> u32 {
+        node.manhattan(target) as u32
+    }
+}
+
+/// the cheapest path from `src` to `dst`
+pub fn find_path<G: Grid>(grid: &G, src: G::Node, dst: G::Node, max_expansions: Option<usize>) -> Option<Path<G::Node>> {
+    search(grid, src, |n| n == dst, |n| grid.heuristic(n, dst), max_expansions)
+}
+
+/// the cheapest path from `src` to the closest of `targets`, the heuristic is the minimum
+/// over the targets, so the list of the targets should be short
+pub fn find_path_to_any<G: Grid>(grid: &G, src: G::Node, targets: &[G::Node], max_expansions: Option<usize>) -> Option<Path<G::Node>> {
+    let h = |n: G::Node| targets.iter().map(|&t| grid.heuristic(n, t)).min().unwrap_or(0);
+    search(grid, src, |n| targets.contains(&n), h, max_expansions)
+}
+
+/// the cheapest path from `src` to the nearest node satisfying the predicate,
+/// e.g. the nearest owned cell, there is no heuristic, so it's Dijkstra's algorithm
+pub fn find_nearest<G: Grid>(grid: &G, src: G::Node, is_target: impl Fn(G::Node) -> bool, max_expansions: Option<usize>) -> Option<Path<G::Node>> {
+    search(grid, src, is_target, |_| 0, max_expansions)
+}
+
+fn search<G: Grid>(
+    grid: &G, src: G::Node,
+    is_target: impl Fn(G::Node) -> bool,
+    heuristic: impl Fn(G::Node) -> u32,
+    max_expansions: Option<usize>,
+) -> Option<Path<G::Node>> {
+    // the best known cost of the node and where we came from
+    let mut best: HashMap<G::Node, (u32, G::Node)> = HashMap::new();
+    let mut closed: HashMap<G::Node, G::Node> = HashMap::new();
+    // the min-heap by (f, h, node), the stale entries are skipped when popped
+    let mut open = BinaryHeap::new();
+    let mut neighbors = vec![];
+    best.insert(src, (0, src));
+    open.push(Reverse((heuristic(src), heuristic(src), src, 0)));
+    let mut expanded = 0;
+    while let Some(Reverse((_, _, node, g))) = open.pop() {
+        if closed.contains_key(&node) || best.get(&node).map(|&(bg, _)| bg < g).unwrap_or(false) {
+            continue;
+        }
+        if max_expansions.map(|max| expanded >= max).unwrap_or(false) {
+            return None;
+        }
+        expanded += 1;
+        closed.insert(node, best[&node].1);
+        if is_target(node) {
+            let mut nodes = vec![node];
+            let mut cur = node;
+            while cur != src {
+                cur = closed[&cur];
+                nodes.push(cur);
+            }
+            nodes.reverse();
+            return Some(Path { nodes, cost: g, expanded });
+        }
+        neighbors.clear();
+        grid.neighbors(node, &mut neighbors);
+        for &(next, cost) in &neighbors {
+            let next_g = g + cost;
+            if closed.contains_key(&next) || best.get(&next).map(|&(bg, _)| bg <= next_g).unwrap_or(false) {
+                continue;
+            }
+            best.insert(next, (next_g, node));
+            let h = heuristic(next);
+            open.push(Reverse((next_g + h, h, next, next_g)));
+        }
+    }
+    None
+}
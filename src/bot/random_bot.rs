@@ -117,17 +117,8 @@ impl RandomBot {
     fn calculate_heads(&self, old_head: Point, mv: Move) -> (Point, Point) {
         let m = self.m as i16;
         let n = self.n as i16;
-        let (di, dj) = match mv {
-            Move::Right => (0, 1),
-            Move::Up    => (-1, 0),
-            Move::Left  => (0, -1),
-            Move::Down  => (1, 0),
-            Move::Stop  => (0, 0),
-        };
-        let new_head = Point(
-            (old_head.0 + di).bound(0, m - 1),
-            (old_head.1 + dj).bound(0, n - 1)
-        );
+        let Point(i, j) = old_head.next(mv);
+        let new_head = Point(i.bound(0, m - 1), j.bound(0, n - 1));
         (old_head, new_head)
     }
 
@@ -0,0 +1,109 @@
+//! Coordinates on the field.
+//!
+//! `Point(i, j)` is the row and the column, the row 0 is on the top, this is how the field
+//! is stored and printed, the bots and the path finding work with it too. `P(x, y)` is Cartesian
+//! with `y` going up, so the row 0 is `y = m - 1`, it's only converted to and from `Point`,
+//! the conversions need the height `m` of the field.
+
+use crate::model::Move;
+use core::fmt;
+
+/// (row, column)
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Point(pub i16, pub i16);
+
+/// Decartes coordinates, (x, y)
+/// make our own coordinate system, in the name of René Descartes
+/// ^ y
+/// |
+/// |
+/// +-------> x
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct P(pub i16, pub i16);
+
+impl Move {
+    /// the moves changing the position
+    pub const ALL: [Move; 4] = [Move::Right, Move::Up, Move::Left, Move::Down];
+
+    /// the change of (row, column)
+    pub fn delta(self) -> (i16, i16) {
+        match self {
+            Move::Right => (0, 1),
+            Move::Up => (-1, 0),
+            Move::Left => (0, -1),
+            Move::Down => (1, 0),
+            Move::Stop => (0, 0),
+        }
+    }
+
+    /// the move changing (row, column) by `(di, dj)`, none if there is no such move
+    pub fn from_delta(di: i16, dj: i16) -> Option<Move> {
+        match (di, dj) {
+            (0, 1) => Some(Move::Right),
+            (-1, 0) => Some(Move::Up),
+            (0, -1) => Some(Move::Left),
+            (1, 0) => Some(Move::Down),
+            (0, 0) => Some(Move::Stop),
+            _ => None,
+        }
+    }
+}
+
+impl Point {
+    /// the adjacent point in the direction of the move, the bounds are not checked
+    pub fn next(self, mv: Move) -> Point {
+        let (di, dj) = mv.delta();
+        Point(self.0 + di, self.1 + dj)
+    }
+
+    /// the adjacent points in the order of `Move::ALL`, the bounds are not checked
+    pub fn neighbors(self) -> impl Iterator<Item = Point> {
+        Move::ALL.iter().map(move |&mv| self.next(mv))
+    }
+
+    /// the move from the point to the adjacent one, none if they are not adjacent
+    pub fn direction_to(self, other: Point) -> Option<Move> {
+        Move::from_delta(other.0 - self.0, other.1 - self.1)
+    }
+
+    /// the point is on the field of `m` rows and `n` columns
+    pub fn is_inside(self, m: usize, n: usize) -> bool {
+        0 <= self.0 && (self.0 as usize) < m && 0 <= self.1 && (self.1 as usize) < n
+    }
+
+    pub fn manhattan(self, other: Point) -> i16 {
+        (self.0 - other.0).abs() + (self.1 - other.1).abs()
+    }
+
+    /// the same cell in Cartesian coordinates on the field of `m` rows
+    pub fn to_cartesian(self, m: usize) -> P {
+        P(self.1, m as i16 - 1 - self.0)
+    }
+}
+
+impl P {
+    /// the same cell in (row, column) on the field of `m` rows
+    pub fn to_point(self, m: usize) -> Point {
+        Point(m as i16 - 1 - self.1, self.0)
+    }
+}
+
+/// both are printed as `(a,b)`
+macro_rules! impl_pair_fmt {
+    ($t:ident) => {
+        impl fmt::Debug for $t {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                write!(fmt, "({},{})", self.0, self.1)
+            }
+        }
+
+        impl fmt::Display for $t {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                write!(fmt, "({},{})", self.0, self.1)
+            }
+        }
+    };
+}
+
+impl_pair_fmt!(Point);
+impl_pair_fmt!(P);
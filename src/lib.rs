@@ -1,5 +1,11 @@
 #![crate_type = "lib"]
 
+pub mod board;
 pub mod bot;
+pub mod coord;
 pub mod model;
+pub mod render;
+pub mod replay;
+pub mod tournament;
 pub mod utils;
+pub mod viewer;
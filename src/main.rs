@@ -1,70 +1,462 @@
-#![allow(unused)]
-
 use std::cell::RefCell;
-use std::time::Duration;
-use std::collections::HashMap;
-use std::cmp::Ordering;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+use std::sync::mpsc::RecvTimeoutError;
 use std::thread;
-use priority_queue::PriorityQueue;
+use std::time::{Duration, Instant};
 use rand::IsaacRng;
-use rand::prelude::RngCore;
+use rand::prelude::{FromEntropy, RngCore};
 use rand::SeedableRng;
+use console::Term;
 
 use xcg::model::*;
-use xcg::bot::KillerBot;
-use xcg::utils::Trim;
-use xcg::bot::common::{P, a_star_find};
-use xcg::bot::common::distance;
-use xcg::bot::common::Weight;
+use xcg::bot::{Budget, ExternalBot, HumanBot, KillerBot, MctsBot, ParanoidBot, RandomBot};
+use xcg::render::{render_html, render_svg, write_asciicast};
+use xcg::replay::{load_replay, save_replay};
+use xcg::tournament;
+use xcg::tournament::{Entry, Job, Pairing, Settings};
+use xcg::utils::{spawn_key_reader, TermGuard};
+use xcg::viewer::ViewerState;
+
+const USAGE: &str = r#"Usage: xcg-app <command> [options]
+
+Commands:
+    play                   watch one match
+    replay <file>          watch the saved replay
+    view <file>            browse the saved replay interactively: pause, step back and forth,
+                           jump to a tick, the keys are shown under the field
+    render <file>          render the saved replay to the HTML page, or to the asciicast v2
+                           recording if the output ends with .cast, or the final state to SVG
+                           if the output ends with .svg
+    tournament             run the bots against each other, print the results as tab separated
+                           values and the standings to stderr
+    bench                  run many matches, print how fast they are
+    parse-check <file>     check the game state file can be parsed
+
+Options:
+    -m, --height <n>       field height [default: 32]
+    -n, --width <n>        field width [default: 54]
+        --map <file>       play on the custom field instead of the rectangle
+    -b, --bots <list>      comma separated bots: killer, random, external:<program>,
+                           mcts, mcts:<iterations>, mcts:<ms>ms, paranoid, paranoid:<depth>,
+                           human, human:<ms> (arrows or WASD, <ms> is the tick) (play)
+                           [default: killer,killer,killer,killer]
+    -s, --seed <n>         the seed of the match (play), the seed of the seeds (tournament, bench)
+    -d, --duration <n>     maximal number of ticks [default: 1024]
+    -r, --ratio <f>        the match is over when this part of the field is filled [default: 0.95]
+    -c, --count <n>        number of matches (bench), number of seeds per group (tournament)
+                           [default: 100]
+    -p, --players <n>      players in a match (tournament) [default: min(4, bots)]
+        --swiss <rounds>   swiss pairing instead of round-robin (tournament)
+        --no-rotation      don't rotate the seats (tournament)
+        --seats            add the column of the roster indices of the players (tournament)
+    -j, --jobs <n>         number of threads to run the matches (tournament, bench) [default: 1]
+    -t, --delay <ms>       frame delay (play, replay, view, render to .cast) [default: 40]
+    -o, --output <file>    save the replay of the match (play), the rendered file (render)
+                           [default: stdout for render]
+        --binary           save the replay in the binary format
+        --simultaneous     all bots move at once instead of one by one
+        --no-color         don't use colors
+    -h, --help             show this message
+"#;
+
+/// the default budget of `mcts` bots, about a millisecond per simulation on the default field
+const MCTS_ITERATIONS: u32 = 200;
+/// the default number of rounds `paranoid` bots look ahead
+const PARANOID_DEPTH: usize = 4;
+/// the default tick of `human` bots in milliseconds
+const HUMAN_PACE: u64 = 300;
+
+#[derive(Clone, Debug)]
+struct Options {
+    height: usize,
+    width: usize,
+    map: Option<String>,
+    bots: Vec<String>,
+    seed: Option<u64>,
+    duration: u32,
+    ratio: f32,
+    count: usize,
+    players: Option<usize>,
+    swiss: Option<usize>,
+    rotate: bool,
+    seats: bool,
+    jobs: usize,
+    delay: u64,
+    output: Option<String>,
+    binary: bool,
+    rules: Rules,
+    color: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            height: 32,
+            width: 54,
+            map: None,
+            bots: vec!["killer".to_string(); 4],
+            seed: None,
+            duration: 1024,
+            ratio: 0.95,
+            count: 100,
+            players: None,
+            swiss: None,
+            rotate: true,
+            seats: false,
+            jobs: 1,
+            delay: 40,
+            output: None,
+            binary: false,
+            rules: Rules::Sequential,
+            color: true,
+        }
+    }
+}
 
 fn main() {
-    let random = RefCell::new(IsaacRng::seed_from_u64(234));
-    let m = 32;
-    let n = 54;
-//    let m = 32;
-//    let n = 54;
-    let timeout = 40;
-
-    let a = KillerBot::new(0);
-    let b = KillerBot::new(1);
-    let c = KillerBot::new(2);
-    let d = KillerBot::new(3);
-//    let mut bots: [Box<dyn Bot>; 2] = [Box::new(a), Box::new(b)];
-    let mut bots: [Box<dyn Bot>; 4] = [Box::new(a), Box::new(b), Box::new(c), Box::new(d)];
-    let names: Vec<String> = bots.iter().enumerate()
-        .map(|(k, _)| ((('A' as u8) + (k as u8)) as char).to_string())
-        .collect();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (command, params, opts) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let result = match (command.as_str(), params.as_slice()) {
+        ("play", []) => play(&opts),
+        ("replay", [file]) => replay(file, &opts),
+        ("view", [file]) => view(file, &opts),
+        ("render", [file]) => render(file, &opts),
+        ("tournament", []) => tournament(&opts),
+        ("bench", []) => bench(&opts),
+        ("parse-check", [file]) => parse_check(file),
+        ("help", []) => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("Invalid command: {} {}", command, params.join(" "))),
+    };
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(String, Vec<String>, Options), String> {
+    let mut opts = Options::default();
+    let mut positional = vec![];
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = |name: &str| it.next().cloned().ok_or_else(|| format!("No value for {}", name));
+        match arg.as_str() {
+            "-m" | "--height" => opts.height = parse_value(arg, &value(arg)?)?,
+            "-n" | "--width" => opts.width = parse_value(arg, &value(arg)?)?,
+            "--map" => opts.map = Some(value(arg)?),
+            "-b" | "--bots" => opts.bots = value(arg)?.split(',').map(|s| s.trim().to_string()).collect(),
+            "-s" | "--seed" => opts.seed = Some(parse_value(arg, &value(arg)?)?),
+            "-d" | "--duration" => opts.duration = parse_value(arg, &value(arg)?)?,
+            "-r" | "--ratio" => opts.ratio = parse_value(arg, &value(arg)?)?,
+            "-c" | "--count" => opts.count = parse_value(arg, &value(arg)?)?,
+            "-p" | "--players" => opts.players = Some(parse_value(arg, &value(arg)?)?),
+            "--swiss" => opts.swiss = Some(parse_value(arg, &value(arg)?)?),
+            "--no-rotation" => opts.rotate = false,
+            "--seats" => opts.seats = true,
+            "-j" | "--jobs" => opts.jobs = parse_value(arg, &value(arg)?)?,
+            "-t" | "--delay" => opts.delay = parse_value(arg, &value(arg)?)?,
+            "-o" | "--output" => opts.output = Some(value(arg)?),
+            "--binary" => opts.binary = true,
+            "--simultaneous" => opts.rules = Rules::Simultaneous,
+            "--no-color" => opts.color = false,
+            "-h" | "--help" => positional.insert(0, "help".to_string()),
+            s if s.starts_with('-') => return Err(format!("Unknown option: {}", s)),
+            s => positional.push(s.to_string()),
+        }
+    }
+    if opts.height < 3 || opts.width < 3 {
+        return Err("The field must be at least 3x3".to_string());
+    }
+    if opts.bots.is_empty() || opts.bots.len() > MAX_PLAYERS {
+        return Err(format!("There must be from 1 to {} bots", MAX_PLAYERS));
+    }
+    if positional.is_empty() {
+        return Err("No command".to_string());
+    }
+    let command = positional.remove(0);
+    Ok((command, positional, opts))
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
+
+/// the field from `--map` or the default one
+fn load_field(opts: &Options) -> Result<Field, String> {
+    match opts.map {
+        Some(ref file) => {
+            let content = fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+            Field::parse_string(&content).map_err(|e| format!("{}:{}", file, e))
+        }
+        None => Ok(create_default_field(opts.height, opts.width)),
+    }
+}
+
+fn create_bot(kind: &str, k: usize) -> Result<Box<dyn Bot>, String> {
+    let bot: Box<dyn Bot> = match kind {
+        "killer" => Box::new(KillerBot::new(k)),
+        "random" => Box::new(RandomBot::new(k as u8)),
+        "mcts" => Box::new(MctsBot::new(k, Budget::Iterations(MCTS_ITERATIONS))),
+        s if s.starts_with("mcts:") => {
+            let budget = &s["mcts:".len()..];
+            let budget = if budget.ends_with("ms") {
+                budget[..budget.len() - 2].parse().ok().map(|ms| Budget::Time(Duration::from_millis(ms)))
+            } else {
+                budget.parse().ok().map(Budget::Iterations)
+            };
+            let budget = budget.ok_or_else(|| format!("Invalid budget of the bot {}: {}", k, s))?;
+            Box::new(MctsBot::new(k, budget))
+        }
+        "paranoid" => Box::new(ParanoidBot::new(k, PARANOID_DEPTH)),
+        s if s.starts_with("paranoid:") => {
+            let depth = s["paranoid:".len()..].parse().map_err(|_| format!("Invalid depth of the bot {}: {}", k, s))?;
+            Box::new(ParanoidBot::new(k, depth))
+        }
+        "human" => Box::new(HumanBot::new(k, spawn_key_reader()).with_pace(Duration::from_millis(HUMAN_PACE))),
+        s if s.starts_with("human:") => {
+            let pace = s["human:".len()..].parse().map_err(|_| format!("Invalid pace of the bot {}: {}", k, s))?;
+            Box::new(HumanBot::new(k, spawn_key_reader()).with_pace(Duration::from_millis(pace)))
+        }
+        s if s.starts_with("external:") => {
+            let mut words = s["external:".len()..].split_whitespace();
+            let program = words.next().ok_or_else(|| format!("No program for the bot {}", k))?;
+            let args: Vec<&str> = words.collect();
+            Box::new(ExternalBot::new(program, &args))
+        }
+        s => return Err(format!("Unknown bot: {}", s)),
+    };
+    Ok(bot)
+}
+
+fn create_bots(kinds: &[String]) -> Result<Vec<Box<dyn Bot>>, String> {
+    // the humans would steal the keys from each other
+    if kinds.iter().filter(|kind| is_human(kind)).count() > 1 {
+        return Err("Only one human can play".to_string());
+    }
+    kinds.iter().enumerate().map(|(k, kind)| create_bot(kind, k)).collect()
+}
 
+fn is_human(kind: &str) -> bool {
+    kind == "human" || kind.starts_with("human:")
+}
+
+fn bot_names(kinds: &[String]) -> Vec<String> {
+    (0..kinds.len()).map(player_name).collect()
+}
+
+/// the seeds of the matches, generated from the single seed
+fn match_seeds(opts: &Options) -> Vec<u64> {
+    let mut random = match opts.seed {
+        Some(seed) => IsaacRng::seed_from_u64(seed),
+        None => IsaacRng::from_entropy(),
+    };
+    (0..opts.count).map(|_| random.next_u64()).collect()
+}
+
+fn play(opts: &Options) -> Result<(), String> {
+    let human = opts.bots.iter().any(|kind| is_human(kind));
+    if human && !Term::stdout().is_term() {
+        return Err("human needs a terminal".to_string());
+    }
+    // before the key reader starts
+    let guard = TermGuard::new();
+    let mut bots = create_bots(&opts.bots)?;
+    let names = bot_names(&opts.bots);
+    // the match is always seeded, so that it can be replayed
+    let seed = opts.seed.unwrap_or_else(|| IsaacRng::from_entropy().next_u64());
+    let logger = |gs: &GameState| {
+        if human {
+            // the keys are read in the raw mode, so the lines need \r, redraw in place
+            print!("\x1B[H\x1B[2J{}\r\n", prettify_game_state(gs, false, opts.color).replace('\n', "\r\n"));
+            let _ = io::stdout().flush();
+        } else if gs.stats.iteration > 0 {
+            println!("{}", prettify_game_state(gs, true, opts.color));
+            thread::sleep(Duration::from_millis(opts.delay));
+        }
+    };
+    let mut the_match = create_match_on(load_field(opts)?, &names, opts.duration, opts.ratio, Some(seed));
+    the_match.rules = opts.rules;
+    let replay = run_match(&mut the_match, &mut bots, &logger);
+    drop(guard);
+    println!("{}", prettify_game_state(&the_match.game_state, false, opts.color));
+    println!("seed: {}", seed);
+    if let Some(ref output) = opts.output {
+        save_replay(output, &replay, opts.binary).map_err(|e| format!("{}: {}", output, e))?;
+    }
+    Ok(())
+}
+
+fn replay(file: &str, opts: &Options) -> Result<(), String> {
+    let replay = load_replay(file).map_err(|e| format!("{}: {}", file, e))?;
+    // the logger is called after every player's move, show only the whole ticks
+    let per_tick = match replay.rules {
+        Rules::Sequential => replay.player_names.len().max(1),
+        Rules::Simultaneous => 1,
+    };
+    let calls = RefCell::new(0);
     let logger = |gs: &GameState| {
-        if gs.stats.iteration > 0 {
-            println!("{}", prettify_game_state(gs, true, true));
-            thread::sleep(Duration::from_millis(timeout));
+        *calls.borrow_mut() += 1;
+        if *calls.borrow() % per_tick == 0 {
+            println!("{}", prettify_game_state(gs, true, opts.color));
+            thread::sleep(Duration::from_millis(opts.delay));
         }
     };
+    let gs = run_replay(&replay, &logger);
+    println!("{}", prettify_game_state(&gs, false, opts.color));
+    Ok(())
+}
 
-    let count = 100_000;
-    let random = RefCell::new(IsaacRng::seed_from_u64(234));
-    let mut seeds = Vec::with_capacity(count);
-    for it in 0..count {
-        let match_k_seed = random.borrow_mut().next_u64();
-        seeds.push(match_k_seed);
-    }
-
-    for it in 0..1 {
-//        let seed = random.borrow_mut().next_u64();
-        let seed = 10591930711989851205;
-//        let seed = seeds[it];
-        let mut match_k = create_match(m, n, &names, 1024, 0.95, Some(seed));
-        let _replay_k = run_match(&mut match_k, &mut bots, &logger);
-//        println!("{} {:?}", "\n".repeat(m + names.len()), match_k.game_state.stats);
-        let stats = match_k.game_state.stats.clone();
-        let i = stats.iteration;
-        let o = stats.ouroboros_count;
-        let b = stats.bite_count;
-        let h = stats.head_to_head_count;
-        let s = stats.scores;
-        println!("{:06}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", it, i, o, b, h, seed, s[0], s[1], s[2], s[3]);
-//        println!("{:06}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", it, i, o, b, h, seed, s[0], s[1]);
-        println!("{}", prettify_game_state(&match_k.game_state, false, true));
+fn view(file: &str, opts: &Options) -> Result<(), String> {
+    let replay = load_replay(file).map_err(|e| format!("{}: {}", file, e))?;
+    if !Term::stdout().is_term() {
+        return Err("view needs a terminal".to_string());
+    }
+    let _guard = TermGuard::new();
+    let keys = spawn_key_reader();
+    let mut viewer = ViewerState::new(&replay, Duration::from_millis(opts.delay));
+    loop {
+        // the terminal is in the raw mode while the key is being read, so the lines need \r
+        print!("\x1B[H\x1B[2J{}", viewer.render(opts.color).replace('\n', "\r\n"));
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        let key = if viewer.playing {
+            match keys.recv_timeout(viewer.delay) {
+                Ok(key) => Some(key),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match keys.recv() {
+                Ok(key) => Some(key),
+                Err(_) => break,
+            }
+        };
+        match key {
+            Some(key) => if !viewer.on_key(key) { break },
+            None => viewer.advance(),
+        }
     }
+    Ok(())
+}
+
+fn render(file: &str, opts: &Options) -> Result<(), String> {
+    let replay = load_replay(file).map_err(|e| format!("{}: {}", file, e))?;
+    match opts.output {
+        Some(ref output) => {
+            let content = if output.ends_with(".svg") {
+                render_svg(&run_replay(&replay, &|_| {})).into_bytes()
+            } else if output.ends_with(".cast") {
+                let mut buf = vec![];
+                let delay = Duration::from_millis(opts.delay);
+                write_asciicast(&mut buf, &replay, delay, opts.color).map_err(|e| e.to_string())?;
+                buf
+            } else {
+                render_html(&replay).into_bytes()
+            };
+            fs::write(output, content).map_err(|e| format!("{}: {}", output, e))
+        }
+        None => {
+            print!("{}", render_html(&replay));
+            Ok(())
+        }
+    }
+}
+
+/// the roster with an entry per bot kind in the options
+fn create_roster(kinds: &[String]) -> Result<Vec<Entry>, String> {
+    // check the bots before the start, so the factories don't fail
+    create_bots(kinds)?;
+    Ok(kinds.iter().zip(bot_names(kinds)).map(|(kind, name)| {
+        let kind = kind.clone();
+        Entry {
+            name: format!("{}:{}", name, kind),
+            factory: Box::new(move |k| create_bot(&kind, k).expect("checked bot")),
+        }
+    }).collect())
+}
+
+fn tournament(opts: &Options) -> Result<(), String> {
+    let roster = create_roster(&opts.bots)?;
+    let players = opts.players.unwrap_or_else(|| roster.len().min(4));
+    if players == 0 || players > roster.len() {
+        return Err(format!("Invalid number of players: {}", players));
+    }
+    let settings = Settings {
+        height: opts.height,
+        width: opts.width,
+        duration: opts.duration,
+        ratio: opts.ratio,
+        players,
+        matches: opts.count,
+        rotate: opts.rotate,
+        pairing: opts.swiss.map_or(Pairing::RoundRobin, |rounds| Pairing::Swiss { rounds }),
+        seed: opts.seed.unwrap_or_else(|| IsaacRng::from_entropy().next_u64()),
+        map: Some(load_field(opts)?),
+        rules: opts.rules,
+        workers: opts.jobs,
+    };
+    tournament::write_matches_header(&mut std::io::stdout(), players, opts.seats).map_err(|e| e.to_string())?;
+    let it = RefCell::new(0);
+    let logger = |result: &tournament::MatchResult| {
+        let stdout = std::io::stdout();
+        tournament::write_match_row(&mut stdout.lock(), *it.borrow(), result, opts.seats).expect("stdout");
+        *it.borrow_mut() += 1;
+    };
+    let result = tournament::run_tournament(&settings, &roster, &logger);
+    tournament::write_standings_tsv(&mut std::io::stderr(), &result.standings).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn bench(opts: &Options) -> Result<(), String> {
+    let roster = create_roster(&opts.bots)?;
+    let settings = Settings {
+        height: opts.height,
+        width: opts.width,
+        duration: opts.duration,
+        ratio: opts.ratio,
+        players: roster.len(),
+        matches: opts.count,
+        rotate: false,
+        pairing: Pairing::RoundRobin,
+        seed: 0,
+        map: Some(load_field(opts)?),
+        rules: opts.rules,
+        workers: opts.jobs,
+    };
+    let seats: Vec<usize> = (0..roster.len()).collect();
+    let jobs: Vec<Job> = match_seeds(opts).into_iter().map(|seed| Job { seed, seats: seats.clone() }).collect();
+    let started = Instant::now();
+    let results = tournament::run_batch(&settings, &roster, &jobs);
+    let elapsed = started.elapsed();
+    let ticks: u64 = results.iter().map(|r| u64::from(r.stats.iteration)).sum();
+    let mut thinking = vec![Duration::from_millis(0); roster.len()];
+    for result in &results {
+        for (k, timing) in result.timings.iter().enumerate() {
+            thinking[k] += timing.total;
+        }
+    }
+    let secs = elapsed.as_secs_f64();
+    println!("matches: {}, ticks: {}, time: {:.3}s, threads: {}", opts.count, ticks, secs, opts.jobs.max(1));
+    println!("matches/s: {:.2}, ticks/s: {:.1}", opts.count as f64 / secs, ticks as f64 / secs);
+    for (k, entry) in roster.iter().enumerate() {
+        println!("{}: {:.3}s thinking", entry.name, thinking[k].as_secs_f64());
+    }
+    Ok(())
+}
+
+fn parse_check(file: &str) -> Result<(), String> {
+    let content = fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+    let gs = GameState::parse_string(&content).map_err(|e| format!("{}:{}", file, e))?;
+    println!("{}: {}x{} field, {} players, {:?}", file, gs.field.m, gs.field.n, gs.players.len(), gs.stats);
+    Ok(())
 }
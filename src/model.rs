@@ -14,7 +14,12 @@ use std::cmp::Ordering;
 use std::borrow::Borrow;
 use std::borrow::BorrowMut;
 use std::cell::RefCell;
+use std::time::{Duration, Instant};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
 use console::Style;
+use crate::board::{BitBoard, Cells};
+pub use crate::coord::Point;
 use core::str;
 use rand::prelude::{Rng, RngCore, FromEntropy};
 use rand::isaac::IsaacRng;
@@ -25,13 +30,11 @@ use itertools::Itertools;
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum Cell {
-    Empty, Border, Owned(u8)
+    /// `Hidden` appears only in the views of the matches with the limited visibility,
+    /// `Void` is a hole in the field, nobody can enter or own it
+    Empty, Border, Owned(u8), Hidden, Void
 }
 
-/// Note `Ord` defined below
-#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd)]
-pub struct Point(pub i16, pub i16);
-
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum Move {
     Right, Up, Left, Down, Stop,
@@ -40,12 +43,12 @@ pub enum Move {
 /// Field contains the information about the terrain
 /// - `m` the number of rows
 /// - `n` the number of cols
-/// - `m×n` matrix of cells
+/// - `m×n` matrix of cells, stored flat, see `Cells`
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Field {
     pub m: usize,
     pub n: usize,
-    pub cells: Vec<Vec<Cell>>,
+    pub cells: Cells,
 }
 
 /// Stats is updated on each step according to the things happened
@@ -89,14 +92,106 @@ pub trait Bot {
     // the bot is mutable
     fn reset(&mut self, gs: &GameStateView, idx: usize, seed: u64);
     fn do_move(&mut self, gs: &GameStateView) -> Move;
+
+    /// the time limit of the move the match enforces, called before `reset`,
+    /// the bot may use it to answer in time
+    fn set_move_timeout(&mut self, _timeout: Duration) {}
 }
 
+/// `move_timeout` limits the time of a single `do_move`, if the bot is late the move is `Stop`,
+/// `time_budget` limits the total time of all the bot's moves, then the bot is forfeited.
+/// `run_match` checks the time after the move is made, `run_timed_match` doesn't wait
+/// for the late bot, the bot still busy with the previous move stays.
 #[derive(Clone, Debug)]
 pub struct Match {
     pub duration: u32,
     pub ratio: f32,
     pub game_state: GameState,
     pub random_seed: Option<u64>,
+    pub move_timeout: Option<Duration>,
+    pub time_budget: Option<Duration>,
+    pub timings: Vec<Timing>,
+    pub visibility: Visibility,
+    pub rules: Rules,
+}
+
+/// Timing is the per player report on how long the bot was thinking
+/// - `total` the sum of all `do_move` durations
+/// - `max` the longest `do_move`
+/// - `overruns` the number of moves replaced with `Stop` because the bot was late
+/// - `forfeited` the bot exceeded `time_budget` and isn't asked for moves anymore
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Timing {
+    pub total: Duration,
+    pub max: Duration,
+    pub overruns: u16,
+    pub forfeited: bool,
+}
+
+/// Undo is the record to revert the single step, see `step_with_undo`
+/// - `cells` the cells changed by the flood with their previous values
+/// - `bodies` the changes of the bodies in the order they were made
+/// - the counters of `Stats` and the score of the moving player before the step
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Undo {
+    pub cells: Vec<(Point, Cell)>,
+    pub bodies: Vec<BodyChange>,
+    pub filled_count: u16,
+    pub head_to_head_count: u16,
+    pub ouroboros_count: u16,
+    pub bite_count: u16,
+    pub score: (usize, u16),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum BodyChange {
+    /// the head was pushed to the body of the player
+    Pushed(usize),
+    /// the body of the player was replaced, the old one is kept
+    Replaced(usize, Vec<Point>),
+}
+
+/// Event is what happened on the step, see `step_with_events`
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Event {
+    /// the player moved to the cell
+    Moved { player: usize, to: Point },
+    /// the player tried to leave the field or to enter the void and stayed
+    Blocked { player: usize },
+    /// the players bumped into each other's heads and stayed
+    HeadToHead { a: usize, b: usize },
+    /// `killer` bit the body of `victim` at the cell, the victim is respawned
+    Bite { killer: usize, victim: usize, at: Point },
+    /// the player bit its own tail at the cell, the player is respawned
+    Ouroboros { player: usize, at: Point },
+    /// the player closed the loop, `cells` is the number of cells captured
+    Captured { player: usize, cells: u16 },
+    /// the dead player appeared at the cell
+    Respawn { player: usize, at: Point },
+}
+
+/// Visibility tells what part of the field the bots see, the rest is `Cell::Hidden`
+/// - `Full` the whole field
+/// - `Radius(r)` the cells within the Euclidean distance `r` from the bot's head or body
+/// - `LineOfSight(r)` the cells within the distance `r` from the head, which are not
+///   behind a border cell, the bot's own body is always seen
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Visibility {
+    #[default]
+    Full,
+    Radius(u16),
+    LineOfSight(u16),
+}
+
+/// Rules tells how the moves are applied
+/// - `Sequential` one by one in the `reordering` order, each bot sees the moves of the previous ones
+/// - `Simultaneous` all bots see the same snapshot, the moves are resolved at once
+///   by `step_simultaneous`
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Rules {
+    #[default]
+    Sequential,
+    Simultaneous,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -108,10 +203,38 @@ pub struct Replay {
     pub player_names: Vec<String>,
     pub moves: Vec<Vec<Move>>,
     pub random_seed: Option<u64>,
+    pub rules: Rules,
+    /// the field the match started on, if it isn't the default one
+    pub map: Option<Field>,
 }
 
-#[derive(Clone, Debug)]
-pub struct ParseError;
+/// ParseError tells what is wrong with the string and where,
+/// `line` and `column` are 1-based
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ParseError {
+    /// the line is `key=value`, but the key is unknown
+    UnknownKey { line: usize, column: usize, key: String },
+    /// the value doesn't have the expected shape
+    MalformedValue { line: usize, column: usize, key: String },
+    /// the text is not a number or the number is too big
+    BadNumber { line: usize, column: usize, text: String },
+    /// `reordering` is not a permutation of the players
+    BadPermutation { line: usize, column: usize },
+    /// the number of scores in `stats` differs from the number of players
+    ScoreCountMismatch { line: usize, column: usize, expected: usize, found: usize },
+    /// the number of `origins` differs from the number of players
+    OriginCountMismatch { line: usize, column: usize, expected: usize, found: usize },
+    /// the field row has different length than the first one
+    RaggedRows { line: usize, column: usize, expected: usize, found: usize },
+    /// the character cannot be in the field
+    UnknownCell { line: usize, column: usize, ch: char },
+    /// the cell is owned by the player, who doesn't exist
+    UnknownOwner { line: usize, column: usize, owner: u8 },
+    /// the player has more than one head
+    DuplicateHead { line: usize, column: usize, player: char },
+    /// the tail cell cannot be reached from the player's head
+    DetachedTail { line: usize, column: usize, player: char },
+}
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct ParseRestResult {
@@ -141,8 +264,50 @@ impl fmt::Debug for dyn Bot {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "bot") }
 }
 
+impl ParseError {
+    pub fn line(&self) -> usize {
+        self.position().0
+    }
+    pub fn column(&self) -> usize {
+        self.position().1
+    }
+    fn position(&self) -> (usize, usize) {
+        match *self {
+            ParseError::UnknownKey { line, column, .. } => (line, column),
+            ParseError::MalformedValue { line, column, .. } => (line, column),
+            ParseError::BadNumber { line, column, .. } => (line, column),
+            ParseError::BadPermutation { line, column } => (line, column),
+            ParseError::ScoreCountMismatch { line, column, .. } => (line, column),
+            ParseError::OriginCountMismatch { line, column, .. } => (line, column),
+            ParseError::RaggedRows { line, column, .. } => (line, column),
+            ParseError::UnknownCell { line, column, .. } => (line, column),
+            ParseError::UnknownOwner { line, column, .. } => (line, column),
+            ParseError::DuplicateHead { line, column, .. } => (line, column),
+            ParseError::DetachedTail { line, column, .. } => (line, column),
+        }
+    }
+}
+
 impl fmt::Display for ParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "ParseError is here!") }
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line(), self.column())?;
+        match self {
+            ParseError::UnknownKey { key, .. } => write!(f, "unknown key '{}'", key),
+            ParseError::MalformedValue { key, .. } => write!(f, "malformed value of '{}'", key),
+            ParseError::BadNumber { text, .. } => write!(f, "bad number '{}'", text),
+            ParseError::BadPermutation { .. } => write!(f, "reordering is not a permutation"),
+            ParseError::ScoreCountMismatch { expected, found, .. } =>
+                write!(f, "expected {} scores, found {}", expected, found),
+            ParseError::OriginCountMismatch { expected, found, .. } =>
+                write!(f, "expected {} origins, found {}", expected, found),
+            ParseError::RaggedRows { expected, found, .. } =>
+                write!(f, "expected the row of length {}, found {}", expected, found),
+            ParseError::UnknownCell { ch, .. } => write!(f, "unknown cell '{}'", ch),
+            ParseError::UnknownOwner { owner, .. } => write!(f, "owner {} is not a player", owner),
+            ParseError::DuplicateHead { player, .. } => write!(f, "player '{}' has more than one head", player),
+            ParseError::DetachedTail { player, .. } => write!(f, "tail '{}' is not connected to the head", player),
+        }
+    }
 }
 
 impl Error for ParseError {
@@ -150,172 +315,232 @@ impl Error for ParseError {
     fn cause(&self) -> Option<&dyn Error> { None }
 }
 
-impl Ord for Point {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.0 < other.0 {
-            Ordering::Less
-        } else if self.0 > other.0 {
-            Ordering::Greater
+impl GameStateView {
+    /// the field rows, preceded by the line `encoding=wide` for more than 10 players
+    pub fn format_string(&self) -> String {
+        let rows = format_rows(&self.field, &self.players).join("\n");
+        if self.players.len() > NARROW_PLAYERS {
+            format!("encoding=wide\n{}", rows)
         } else {
-            self.1.cmp(&other.1)
+            rows
         }
     }
 }
 
-impl fmt::Debug for Point {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_char('(')?;
-        fmt.write_str(&self.0.to_string())?;
-        fmt.write_char(',')?;
-        fmt.write_str(&self.1.to_string())?;
-        fmt.write_char(')')?;
-        Ok(())
-    }
+/// the owners are written with these characters in the first layer, so there are at most 64 players
+pub const OWNER_CHARS: &[u8; 64] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ@$";
+pub const MAX_PLAYERS: usize = 64;
+/// with more players the owner chars overlap with the letters of the players,
+/// the cells are written wide then
+pub const NARROW_PLAYERS: usize = 10;
+
+fn owner_char(k: u8) -> u8 {
+    OWNER_CHARS[k as usize]
 }
 
-impl fmt::Display for Point {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_char('(')?;
-        fmt.write_str(&self.0.to_string())?;
-        fmt.write_char(',')?;
-        fmt.write_str(&self.1.to_string())?;
-        fmt.write_char(')')?;
-        Ok(())
+fn owner_index(c: u8) -> Option<u8> {
+    OWNER_CHARS.iter().position(|&x| x == c).map(|k| k as u8)
+}
+
+/// the default name of the player `k`: `A`...`Z`, then `P26`, `P27` and so on
+pub fn player_name(k: usize) -> String {
+    if k < 26 {
+        ((b'A' + k as u8) as char).to_string()
+    } else {
+        format!("P{}", k)
     }
 }
 
-impl GameStateView {
-    pub fn format_string(&self) -> String {
-        let m = self.field.m;
-        let n = self.field.n;
-        let np = self.players.len();
-        let capacity = m * (2 * n + 1) + 2;
-        let mut result = String::with_capacity(capacity);
-        let mut layer0 = vec![vec![' ' as u8; n]; m];
-        let mut layer1 = vec![vec!['.' as u8; n]; m];
-        for i in 0..m {
-            for j in 0..n {
-                let cell = self.field.cells[i][j];
-                match cell {
-                    Cell::Empty => { layer0[i][j] = ' ' as u8 }
-                    Cell::Border => { layer0[i][j] = '*' as u8 }
-                    Cell::Owned(c) => layer0[i][j] = ('0' as u8) + c,
-                }
+/// The field rows, the cell is the owner char followed by the player mark:
+/// - narrow (up to 10 players) `A` the head and `a` the tail of the player 0, `.` nobody
+/// - wide (more players) `0+` the head and `0-` the tail of the player 0, `..` nobody,
+///   the players are written with `OWNER_CHARS`
+fn format_rows(field: &Field, players: &[Player]) -> Vec<String> {
+    let m = field.m;
+    let n = field.n;
+    let wide = players.len() > NARROW_PLAYERS;
+    let mut layer0 = vec![vec![b' '; n]; m];
+    let mut layer1 = vec![vec![*b".."; n]; m];
+    for i in 0..m {
+        for j in 0..n {
+            layer0[i][j] = match field.cells[i][j] {
+                Cell::Empty => b' ',
+                Cell::Border => b'*',
+                Cell::Owned(c) => owner_char(c),
+                Cell::Hidden => b'?',
+                Cell::Void => b'#',
             }
         }
-        for k in 0..np {
-            let player = &self.players[k].0;
-            let ch = ('A' as u8) + (k as u8);
-            for l in 0..player.len() {
-                let i = player[l].0 as usize;
-                let j = player[l].1 as usize;
-                // if it is the last element == player's head
-                if l == player.len() - 1 {
-                    layer1[i][j] = ch;
-                } else {
-                    layer1[i][j] = ch.to_ascii_lowercase();
-                }
-            }
+    }
+    for (k, player) in players.iter().enumerate() {
+        let body = &player.0;
+        for (l, &Point(i, j)) in body.iter().enumerate() {
+            // if it is the last element == player's head
+            let head = l == body.len() - 1;
+            layer1[i as usize][j as usize] = match (wide, head) {
+                (false, true) => [b'A' + k as u8, b'.'],
+                (false, false) => [b'a' + k as u8, b'.'],
+                (true, true) => [owner_char(k as u8), b'+'],
+                (true, false) => [owner_char(k as u8), b'-'],
+            };
         }
-        // now put all the stuff
-        for i in 0..m {
-            for j in 0..n {
-                result.push(layer0[i][j] as char);
-                result.push(layer1[i][j] as char);
+    }
+    let mut rows = Vec::with_capacity(m);
+    for i in 0..m {
+        let mut row = String::with_capacity(3 * n);
+        for j in 0..n {
+            row.push(layer0[i][j] as char);
+            row.push(layer1[i][j][0] as char);
+            if wide {
+                row.push(layer1[i][j][1] as char);
             }
-            // make sure there is no hanging \n
-            if i != m - 1 { result.push('\n'); }
         }
-        result
+        rows.push(row);
     }
+    rows
+}
+
+/// the rows of the irregular fields may start with the hole
+fn is_field_row(trimmed: &str) -> bool {
+    trimmed.starts_with('*') || trimmed.starts_with('#')
+}
+
+/// the key of the `key=value` line
+fn line_key(s: &str) -> &str {
+    s.split('=').next().unwrap_or("").trim()
+}
+
+/// more than 10 players need 3 chars per cell
+fn is_wide_encoding(s: &str) -> bool {
+    s.split_once('=').map(|(k, v)| (k.trim(), v.trim())) == Some(("encoding", "wide"))
 }
 
 impl GameState {
     pub fn parse_string(str: &str) -> Result<GameState, ParseError> {
-        fn bound(x: i16, l: i16, r: i16) -> i16 {
-            if x < l { l } else if r < x { r } else { x }
-        }
-        let neigh = vec![Point(0, -1), Point(-1, 0), Point(0, 1), Point(1, 0)];
-        // detect sizes
-        let raw_lines: Vec<&str> = str.split("\n")
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .collect();
-        let mut lines: Vec<&str> = Vec::with_capacity(raw_lines.len());
-        let mut rest: Vec<&str> = Vec::new();
-        for s in raw_lines {
-            if s.starts_with("*") {
-                lines.push(s);
+        // detect sizes, remember the line numbers and the columns of the trimmed lines
+        let mut lines: Vec<(usize, usize, &str)> = vec![];
+        let mut rest: Vec<(usize, &str)> = vec![];
+        for (k, s) in str.split('\n').enumerate() {
+            let trimmed = s.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if is_field_row(trimmed) {
+                let offset = s.len() - s.trim_start().len();
+                lines.push((k + 1, offset, trimmed));
             } else {
-                rest.push(s);
+                rest.push((k + 1, s));
             }
         }
+        let wide = rest.iter().any(|(_, s)| is_wide_encoding(s));
+        let cw = if wide { 3 } else { 2 };
         let m = lines.len();
-        let n = lines.iter().map(|it| it.len() / 2).max().unwrap_or(0);
-        let mut layer0 = vec![vec![' ' as u8; n]; m];
-        let mut layer1 = vec![vec!['.' as u8; n]; m];
+        let width = lines.first().map(|(_, _, s)| s.len()).unwrap_or(0);
+        let n = width / cw;
+        for &(line, offset, s) in &lines {
+            if let Some((pos, ch)) = s.char_indices().find(|(_, c)| !c.is_ascii()) {
+                return Err(ParseError::UnknownCell { line, column: offset + s[..pos].chars().count() + 1, ch });
+            }
+            if s.len() != width || !width.is_multiple_of(cw) {
+                return Err(ParseError::RaggedRows { line, column: offset + 1, expected: width - width % cw, found: s.len() });
+            }
+        }
+        // position of the cell (i, j) in the source, layer is 0, 1 or 2
+        let position = |i: usize, j: usize, layer: usize| {
+            let (line, offset, _) = lines[i];
+            (line, offset + cw * j + layer + 1)
+        };
+        // the player mark is (player, is_head), the char is kept for the errors
+        let mut layer0 = vec![vec![b' '; n]; m];
+        let mut layer1: Vec<Vec<Option<(u8, bool, char)>>> = vec![vec![None; n]; m];
         for i in 0..m {
-            let cs = lines[i].as_bytes();
-            for j in 0..(2 * n) {
-                let c = cs[j];
-                if j % 2 == 0 {
-                    layer0[i][j / 2] = c;
+            let cs = lines[i].2.as_bytes();
+            for j in 0..n {
+                layer0[i][j] = cs[cw * j];
+                let c = cs[cw * j + 1];
+                layer1[i][j] = if !wide {
+                    match c {
+                        b'A'..=b'Z' => Some((c - b'A', true, c as char)),
+                        b'a'..=b'z' => Some((c - b'a', false, c as char)),
+                        b'.' | b' ' => None,
+                        _ => {
+                            let (line, column) = position(i, j, 1);
+                            return Err(ParseError::UnknownCell { line, column, ch: c as char });
+                        }
+                    }
                 } else {
-                    layer1[i][j / 2] = c;
-                }
+                    match (c, cs[cw * j + 2], owner_index(c)) {
+                        (b'.', b'.', _) => None,
+                        (_, b'+', Some(k)) => Some((k, true, c as char)),
+                        (_, b'-', Some(k)) => Some((k, false, c as char)),
+                        (_, b'+', None) | (_, b'-', None) => {
+                            let (line, column) = position(i, j, 1);
+                            return Err(ParseError::UnknownCell { line, column, ch: c as char });
+                        }
+                        (_, d, _) => {
+                            let (line, column) = position(i, j, 2);
+                            return Err(ParseError::UnknownCell { line, column, ch: d as char });
+                        }
+                    }
+                };
             }
         }
         let mut players_map: HashMap<u8, Vec<Point>> = HashMap::new();
-        for i in 0..m {
-            for j in 0..n {
-                let c = layer1[i][j];
-                if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-                    players_map.insert(c - ('A' as u8), vec![Point(i as i16, j as i16)]);
+        for (i, row) in layer1.iter().enumerate() {
+            for (j, &mark) in row.iter().enumerate() {
+                if let Some((k, true, ch)) = mark {
+                    if players_map.insert(k, vec![Point(i as i16, j as i16)]).is_some() {
+                        let (line, column) = position(i, j, 1);
+                        return Err(ParseError::DuplicateHead { line, column, player: ch });
+                    }
                 }
             }
         }
         if let Some(&max_id) = players_map.keys().max() {
             for k in 0..=max_id {
-                players_map.entry(k).or_insert_with(|| vec![]);
+                players_map.entry(k).or_default();
             }
         }
+        let np = players_map.len();
         let mut cells: Vec<Vec<Cell>> = vec![vec![Cell::Empty; n]; m];
         for i in 0..m {
             for j in 0..n {
                 let c = layer0[i][j];
-                let cell = if c == ('*' as u8) {
-                    Cell::Border
-                } else if ('0' as u8) <= c && c <= ('9' as u8) {
-                    Cell::Owned(c - ('0' as u8))
-                } else {
-                    Cell::Empty
+                cells[i][j] = match (c, owner_index(c)) {
+                    (b'*', _) => Cell::Border,
+                    (b' ', _) => Cell::Empty,
+                    (b'?', _) => Cell::Hidden,
+                    (b'#', _) => Cell::Void,
+                    (_, Some(k)) if (k as usize) < np => Cell::Owned(k),
+                    (_, Some(k)) => {
+                        let (line, column) = position(i, j, 0);
+                        return Err(ParseError::UnknownOwner { line, column, owner: k });
+                    }
+                    _ => {
+                        let (line, column) = position(i, j, 0);
+                        return Err(ParseError::UnknownCell { line, column, ch: c as char });
+                    }
                 };
-                cells[i][j] = cell;
             }
         }
         // now build player bodies = tails + heads
         // head is the last element of the corresponding list
-        for (k, mut body) in &mut players_map {
+        for (&k, body) in &mut players_map {
             if !body.is_empty() {
                 // current point, start with the head
                 let mut cur_point = Some(body[0]);
-                let ct = ('a' as u8) + k; // the player's tail char
-                while cur_point.is_some() {
-                    // seek for lowercase letter around the current point
+                while let Some(cp) = cur_point {
+                    // seek for the tail mark of the player around the current point
                     // if something found, then add the point to the current body
                     // otherwise consider the body fully built
-                    let cp = cur_point.unwrap();
-                    let point0 = neigh.iter().map(|Point(ni, nj)| {
-                        let Point(mut i, mut j) = cp;
-                        i = bound(i + ni, 0, (m - 1) as i16);
-                        j = bound(j + nj, 0, (n - 1) as i16);
-                        Point(i, j)
-                    }).filter(|p| {
-                        let Point(pi, pj) = p;
-                        !body.contains(p) && layer1[*pi as usize][*pj as usize] == ct
-                    }).next();
-                    if point0.is_some() {
-                        body.insert(0, point0.unwrap());
+                    let point0 = NEIGHBORS.iter()
+                        .map(|&mv| cp.next(mv))
+                        .find(|p| {
+                            p.is_inside(m, n) && !body.contains(p)
+                                && matches!(layer1[p.0 as usize][p.1 as usize], Some((l, false, _)) if l == k)
+                        });
+                    if let Some(p) = point0 {
+                        body.insert(0, p);
                     }
                     cur_point = point0;
                 }
@@ -324,31 +549,32 @@ impl GameState {
                 // so far just skip it
             }
         }
-
-        let np = players_map.len();
-        // calculate statistics
-        let mut filled_count = 0;
-        let mut scores = vec![0u16; np];
-        for i in 0..m {
-            for j in 0..n {
-                match cells[i][j] {
-                    Cell::Empty => {}
-                    Cell::Border => {
-                        filled_count += 1;
-                    }
-                    Cell::Owned(k) => {
-                        filled_count += 1;
-                        scores[k as usize] = scores[k as usize] + 1;
+        // all the tail cells must be collected from the heads
+        for (i, row) in layer1.iter().enumerate() {
+            for (j, &mark) in row.iter().enumerate() {
+                if let Some((k, false, ch)) = mark {
+                    let p = Point(i as i16, j as i16);
+                    let attached = match players_map.get(&k) {
+                        Some(body) => body.contains(&p),
+                        None => false,
+                    };
+                    if !attached {
+                        let (line, column) = position(i, j, 1);
+                        return Err(ParseError::DetachedTail { line, column, player: ch });
                     }
                 }
             }
         }
 
+        let cells = Cells::from(cells);
+        // calculate statistics
+        let (filled_count, scores) = count_filled(&cells, np);
+
         let mut players = Vec::<Player>::with_capacity(np);
         for k in 0..np {
             // to avoid the error 'cannot borrow from indexed context'
             // we need to remove the bodies from players_map
-            let pts = players_map.remove(&(k as u8)).unwrap();
+            let pts = players_map.remove(&(k as u8)).unwrap_or_default();
             players.push(Player(pts));
         }
         let field = Field { m, n, cells };
@@ -356,7 +582,7 @@ impl GameState {
         let triple = GameState::parse_string_rest(np, &rest)?;
         let reordering = triple.reordering.unwrap_or_else(|| create_default_permutation(np));
         let origins = triple.origins.unwrap_or_else(|| create_origins_n(m, n, np));
-        let stats = triple.stats.unwrap_or_else(|| Stats {
+        let stats = triple.stats.unwrap_or(Stats {
             iteration: 0,
             filled_count,
             head_to_head_count: 0,
@@ -364,83 +590,88 @@ impl GameState {
             bite_count: 0,
             scores,
         });
-        let player_names = (0..np).map(|i| ((('A' as u8) + (i as u8)) as char).to_string()).collect();
+        let player_names = (0..np).map(player_name).collect();
         Ok(GameState { field, players, player_names, origins, stats, reordering })
     }
 
-    pub fn parse_string_rest(np: usize, rest: &Vec<&str>) -> Result<ParseRestResult, ParseError> {
+    /// `rest` is the list of `key=value` lines along with their line numbers
+    pub fn parse_string_rest(np: usize, rest: &[(usize, &str)]) -> Result<ParseRestResult, ParseError> {
         let mut reordering: Option<Vec<u8>> = None;
         let mut origins: Option<Vec<Point>> = None;
         let mut stats: Option<Stats> = None;
-        for s in rest {
-            let mut lr = s.split("=");
-            let l = lr.next().unwrap().trim();
-            let r = lr.next().unwrap().trim();
+        let list_re = Regex::new("\\[(.*?)]").unwrap();
+        let stats_re = Regex::new("^Stats\\((\\d+),(\\d+),(\\d+),(\\d+),(\\d+),\\[(.*?)]\\)$").unwrap();
+        let origin_re = Regex::new("\\((\\d+),(\\d+)\\)").unwrap();
+        for &(line, s) in rest {
+            let eq = match s.find('=') {
+                Some(eq) => eq,
+                None => {
+                    let key = s.trim().to_string();
+                    return Err(ParseError::MalformedValue { line, column: s.len() - s.trim_start().len() + 1, key });
+                }
+            };
+            let l = s[..eq].trim();
+            let r = s[eq + 1..].trim();
+            // the column of the value and of anything inside of it
+            let r_offset = s.len() - s[eq + 1..].trim_start().len();
+            let column = |pos: usize| r_offset + pos + 1;
+            let malformed = || ParseError::MalformedValue { line, column: column(0), key: l.to_string() };
             if l == "reordering" {
-                let caps1 = Regex::new("\\[(.*?)]").unwrap().captures(r);
-                if caps1.is_some() {
-                    let caps1 = caps1.unwrap();
-                    let list: Vec<u8> = caps1.get(1).unwrap().as_str()
-                        .split(",")
-                        .map(|s: &str| s.trim().parse::<u8>().unwrap())
-                        .collect();
-                    // check
-                    let all_present = (0..np as u8).all(|x| list.contains(&x));
-                    if list.len() != np || !all_present {
-                        return Err(ParseError);
-                    }
-                    reordering = Some(list);
+                let caps = list_re.captures(r).ok_or_else(malformed)?;
+                let items = caps.get(1).ok_or_else(malformed)?;
+                let list = parse_numbers::<u8>(items, line, &column)?;
+                // check
+                let all_present = (0..np).all(|x| list.contains(&(x as u8)));
+                if list.len() != np || !all_present {
+                    return Err(ParseError::BadPermutation { line, column: column(0) });
                 }
+                reordering = Some(list);
             } else if l == "stats" {
-                let caps1 = Regex::new("Stats\\((.*?)\\)").unwrap().captures(r);
-                if caps1.is_some() {
-                    let caps1 = caps1.unwrap().get(1).unwrap().as_str();
-                    let caps2 = Regex::new("(\\d+),(\\d+),(\\d+),(\\d+),(\\d+),\\[(.*?)]").unwrap().captures(caps1);
-                    if caps2.is_some() {
-                        let c2 = caps2.unwrap();
-                        let a1 = c2.get(1).unwrap().as_str().parse::<u32>().unwrap();
-                        let a2 = c2.get(2).unwrap().as_str().parse::<u16>().unwrap();
-                        let a3 = c2.get(3).unwrap().as_str().parse::<u16>().unwrap();
-                        let a4 = c2.get(4).unwrap().as_str().parse::<u16>().unwrap();
-                        let a5 = c2.get(5).unwrap().as_str().parse::<u16>().unwrap();
-                        let scores: Vec<u16> = c2.get(6).unwrap().as_str()
-                            .split(",")
-                            .map(|s: &str| s.trim().parse::<u16>().unwrap())
-                            .collect();
-                        if scores.len() != np {
-                            return Err(ParseError);
-                        }
-                        stats = Some(Stats {
-                            iteration: a1,
-                            filled_count: a2,
-                            head_to_head_count: a3,
-                            ouroboros_count: a4,
-                            bite_count: a5,
-                            scores,
-                        });
-                    }
+                let caps = stats_re.captures(r).ok_or_else(malformed)?;
+                let number = |k: usize| caps.get(k).ok_or_else(malformed).and_then(|c| parse_number::<u16>(c, line, &column));
+                let iteration = caps.get(1).ok_or_else(malformed).and_then(|c| parse_number::<u32>(c, line, &column))?;
+                let filled_count = number(2)?;
+                let head_to_head_count = number(3)?;
+                let ouroboros_count = number(4)?;
+                let bite_count = number(5)?;
+                let items = caps.get(6).ok_or_else(malformed)?;
+                let scores = parse_numbers::<u16>(items, line, &column)?;
+                if scores.len() != np {
+                    return Err(ParseError::ScoreCountMismatch { line, column: column(items.start()), expected: np, found: scores.len() });
+                }
+                stats = Some(Stats { iteration, filled_count, head_to_head_count, ouroboros_count, bite_count, scores });
+            } else if l == "encoding" {
+                // the encoding is detected before parsing the field
+                if r != "wide" && r != "narrow" {
+                    return Err(malformed());
                 }
             } else if l == "origins" {
-                let caps1 = Regex::new("\\[(.*?)]").unwrap().captures(r);
-                if caps1.is_some() {
-                    let caps1 = caps1.unwrap().get(1).unwrap().as_str();
-                    let caps2 = Regex::new("\\((\\d+),(\\d+)\\),?").unwrap();
-                    let mut list: Vec<Point> = vec![];
-                    for c2 in caps2.captures_iter(caps1) {
-                        let i = c2.get(1).unwrap().as_str().parse::<i16>().unwrap();
-                        let j = c2.get(2).unwrap().as_str().parse::<i16>().unwrap();
-                        list.push(Point(i, j))
-                    }
-                    if list.len() != np {
-                        return Err(ParseError);
-                    }
-                    origins = Some(list);
+                let caps = list_re.captures(r).ok_or_else(malformed)?;
+                let items = caps.get(1).ok_or_else(malformed)?;
+                let mut list: Vec<Point> = vec![];
+                for c in origin_re.captures_iter(items.as_str()) {
+                    let i = c.get(1).ok_or_else(malformed)?;
+                    let j = c.get(2).ok_or_else(malformed)?;
+                    // the positions are relative to `items`
+                    let shifted = |pos: usize| column(items.start() + pos);
+                    let i = parse_number::<i16>(i, line, &shifted)?;
+                    let j = parse_number::<i16>(j, line, &shifted)?;
+                    list.push(Point(i, j))
+                }
+                if list.len() != np {
+                    return Err(ParseError::OriginCountMismatch { line, column: column(items.start()), expected: np, found: list.len() });
                 }
+                origins = Some(list);
+            } else {
+                let column = s.len() - s.trim_start().len() + 1;
+                return Err(ParseError::UnknownKey { line, column, key: l.to_string() });
             }
         }
         Ok(ParseRestResult { reordering, origins, stats })
     }
 
+    /// the field rows (see `format_rows`), then the `key=value` lines unless `field_only`,
+    /// `encoding=wide` is written for more than 10 players
     pub fn format_string(&self, field_only: bool) -> String {
         let m = self.field.m;
         let n = self.field.n;
@@ -448,41 +679,14 @@ impl GameState {
         let capacity = if field_only { m * (2 * n + 1) + 2 } else { m * 2 * (m + n) + 10 * np + 30 };
         let mut result = String::with_capacity(capacity);
 
-        let mut layer0 = vec![vec![' ' as u8; n]; m];
-        let mut layer1 = vec![vec!['.' as u8; n]; m];
-        for i in 0..m {
-            for j in 0..n {
-                let cell = self.field.cells[i][j];
-                match cell {
-                    Cell::Empty => { layer0[i][j] = ' ' as u8 }
-                    Cell::Border => { layer0[i][j] = '*' as u8 }
-                    Cell::Owned(c) => layer0[i][j] = ('0' as u8) + c,
-                }
-            }
-        }
-        for k in 0..np {
-            let player = &self.players[k].0;
-            let ch = ('A' as u8) + (k as u8);
-            for l in 0..player.len() {
-                let i = player[l].0 as usize;
-                let j = player[l].1 as usize;
-                // if it is the last element == player's head
-                if l == player.len() - 1 {
-                    layer1[i][j] = ch;
-                } else {
-                    layer1[i][j] = ch.to_ascii_lowercase();
-                }
-            }
-        }
-        // now put all the stuff
-        for i in 0..m {
-            for j in 0..n {
-                result.push(layer0[i][j] as char);
-                result.push(layer1[i][j] as char);
-            }
+        for row in format_rows(&self.field, &self.players) {
+            result.push_str(&row);
             result.push('\n');
         }
         if !field_only {
+            if np > NARROW_PLAYERS {
+                result.push_str("encoding=wide\n");
+            }
             result.push_str("reordering=[");
             result.push_str(&join(&self.reordering[..], &","));
             result.push_str("]\n");
@@ -548,16 +752,33 @@ impl fmt::Display for GameState {
     }
 }
 
+/// parse comma separated numbers, `column` maps the position in the value to the column
+fn parse_numbers<T: FromStr>(items: RegexMatch, line: usize, column: &dyn Fn(usize) -> usize) -> Result<Vec<T>, ParseError> {
+    let mut result = vec![];
+    if items.as_str().trim().is_empty() {
+        return Ok(result);
+    }
+    let mut pos = items.start();
+    for item in items.as_str().split(',') {
+        let start = pos + item.len() - item.trim_start().len();
+        let text = item.trim();
+        let number = text.parse::<T>()
+            .map_err(|_| ParseError::BadNumber { line, column: column(start), text: text.to_string() })?;
+        result.push(number);
+        pos += item.len() + 1;
+    }
+    Ok(result)
+}
+
+fn parse_number<T: FromStr>(item: RegexMatch, line: usize, column: &dyn Fn(usize) -> usize) -> Result<T, ParseError> {
+    item.as_str().parse::<T>()
+        .map_err(|_| ParseError::BadNumber { line, column: column(item.start()), text: item.as_str().to_string() })
+}
+
 pub fn make_game_state_view(pgs: &mut GameStateView, gs: &GameState, idx: usize) {
-    let m = gs.field.m;
-    let n = gs.field.n;
     let np = gs.players.len();
     pgs.idx = idx;
-    for i in 0..m {
-        for j in 0..n {
-            pgs.field.cells[i][j] = gs.field.cells[i][j]
-        }
-    }
+    pgs.field.cells.clone_from(&gs.field.cells);
     for k in 0..np {
         if k == idx {
             pgs.players[k] = Player(gs.players[k].body().clone());
@@ -571,6 +792,92 @@ pub fn make_game_state_view(pgs: &mut GameStateView, gs: &GameState, idx: usize)
     }
 }
 
+/// hide the cells the bot `pgs.idx` cannot see, and the parts of the opponents on them
+pub fn apply_visibility(pgs: &mut GameStateView, visibility: Visibility) {
+    let m = pgs.field.m;
+    let n = pgs.field.n;
+    let body = pgs.players[pgs.idx].body().clone();
+    let mut visible = vec![vec![false; n]; m];
+    // in i32, the squares of the large radius don't fit i16
+    fn mark_around(visible: &mut [Vec<bool>], field: &Field, center: Point, r: u16, check_line: bool) {
+        let r = (r as usize).min(field.m + field.n) as i32;
+        let (ci, cj) = (i32::from(center.0), i32::from(center.1));
+        for i in (ci - r).max(0)..(ci + r + 1).min(field.m as i32) {
+            for j in (cj - r).max(0)..(cj + r + 1).min(field.n as i32) {
+                let (di, dj) = (i - ci, j - cj);
+                if di * di + dj * dj > r * r || visible[i as usize][j as usize] {
+                    continue;
+                }
+                if !check_line || is_line_clear(field, center, Point(i as i16, j as i16)) {
+                    visible[i as usize][j as usize] = true;
+                }
+            }
+        }
+    }
+    match visibility {
+        Visibility::Full => return,
+        Visibility::Radius(r) => {
+            for &p in &body {
+                mark_around(&mut visible, &pgs.field, p, r, false);
+            }
+        }
+        Visibility::LineOfSight(r) => {
+            if let Some(&head) = body.last() {
+                mark_around(&mut visible, &pgs.field, head, r, true);
+            }
+            for &Point(i, j) in &body {
+                visible[i as usize][j as usize] = true;
+            }
+        }
+    }
+    for (i, row) in visible.iter().enumerate() {
+        for (j, &seen) in row.iter().enumerate() {
+            if !seen {
+                pgs.field.cells.set(Point(i as i16, j as i16), Cell::Hidden);
+            }
+        }
+    }
+    // the opponent without the visible head is hidden entirely, otherwise the last visible
+    // cell of the tail would look like the head
+    let idx = pgs.idx;
+    let is_visible = |&Point(i, j): &Point| visible[i as usize][j as usize];
+    for (k, player) in pgs.players.iter_mut().enumerate() {
+        if k != idx {
+            if player.head().map(is_visible).unwrap_or(false) {
+                player.body_mut().retain(is_visible);
+            } else {
+                player.body_mut().clear();
+            }
+        }
+    }
+}
+
+/// true if no border cell stands strictly between `a` and `b` on the Bresenham line
+fn is_line_clear(field: &Field, a: Point, b: Point) -> bool {
+    let Point(mut i, mut j) = a;
+    let Point(bi, bj) = b;
+    let (di, dj) = ((bi - i).abs(), (bj - j).abs());
+    let (si, sj) = ((bi - i).signum(), (bj - j).signum());
+    let mut err = dj - di;
+    loop {
+        if (i, j) == (bi, bj) {
+            return true;
+        }
+        if Point(i, j) != a && field.cells[i as usize][j as usize] == Cell::Border {
+            return false;
+        }
+        let e2 = 2 * err;
+        if e2 > -di {
+            err -= di;
+            j += sj;
+        }
+        if e2 < dj {
+            err += dj;
+            i += si;
+        }
+    }
+}
+
 pub fn create_default_permutation(np: usize) -> Vec<u8> {
     (0..np).map(|x| x as u8).collect()
 }
@@ -636,19 +943,68 @@ pub fn create_origins(height: usize, width: usize, perm: &Vec<u8>) -> Vec<Point>
 pub fn create_default_field(height: usize, width: usize) -> Field {
     let m = height;
     let n = width;
-    let mut cells: Vec<Vec<Cell>> = vec![vec![Cell::Empty; n]; m];
+    let mut cells = Cells::new(m, n, Cell::Empty);
     for i in 0..m {
         for j in 0..n {
-            cells[i][j] = if i == 0 || i == m - 1 || j == 0 || j == n - 1 {
-                Cell::Border
-            } else {
-                Cell::Empty
+            if i == 0 || i == m - 1 || j == 0 || j == n - 1 {
+                cells.set(Point(i as i16, j as i16), Cell::Border);
             }
         }
     }
     Field { m, n, cells }
 }
 
+impl Field {
+    /// Parse the map, that is the field in the `format_string` format where only
+    /// `*` (border), ` ` (empty) and `#` (hole) are expected in the first layer
+    /// and ` ` or `.` in the second one, the rows may start with `#` to make the irregular
+    /// shapes, the owners and the players are rejected, the `key=value` lines other than
+    /// `encoding` are ignored
+    pub fn parse_string(str: &str) -> Result<Field, ParseError> {
+        // the ignored lines are blanked, so that the errors point to the same lines
+        let lines: Vec<&str> = str.split('\n')
+            .map(|s| if is_field_row(s.trim()) || line_key(s) == "encoding" { s } else { "" })
+            .collect();
+        let gs = GameState::parse_string(&lines.join("\n"))?;
+        let cw = if lines.iter().any(|s| is_wide_encoding(s)) { 3 } else { 2 };
+        // the same position the game state parser reports, with the char found there
+        let unknown = |i: usize, j: usize, layer: usize| {
+            let (line, column, ch) = lines.iter().enumerate()
+                .filter(|(_, s)| is_field_row(s.trim()))
+                .nth(i)
+                .map(|(k, s)| {
+                    let column = s.len() - s.trim_start().len() + cw * j + layer;
+                    (k + 1, column + 1, s[column..].chars().next().unwrap_or('?'))
+                })
+                .unwrap_or((0, 0, '?'));
+            ParseError::UnknownCell { line, column, ch }
+        };
+        for (i, row) in gs.field.cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if let Cell::Hidden | Cell::Owned(_) = cell {
+                    return Err(unknown(i, j, 0));
+                }
+            }
+        }
+        if let Some(&Point(i, j)) = gs.players.iter().flat_map(|p| p.body().first()).next() {
+            return Err(unknown(i as usize, j as usize, 1));
+        }
+        Ok(gs.field)
+    }
+
+    /// the number of cells that can be filled, i.e. all but the holes
+    pub fn playable_count(&self) -> usize {
+        self.cells.as_slice().iter().filter(|&&c| c != Cell::Void).count()
+    }
+}
+
+/// the number of the border and owned cells, and the scores of the `np` players
+fn count_filled(cells: &Cells, np: usize) -> (u16, Vec<u16>) {
+    let scores: Vec<u16> = (0..np).map(|k| cells.owned_count(k) as u16).collect();
+    let borders = cells.as_slice().iter().filter(|&&c| c == Cell::Border).count() as u16;
+    (borders + scores.iter().sum::<u16>(), scores)
+}
+
 pub fn border_to_point(height: usize, width: usize, pos: usize) -> Point {
     let m = height as i16;
     let n = width as i16;
@@ -664,66 +1020,94 @@ pub fn border_to_point(height: usize, width: usize, pos: usize) -> Point {
     }
 }
 
-const NEIGHBORS: &[(i16, i16)] = &[(0, -1), (-1, 0), (0, 1), (1, 0)];
+const NEIGHBORS: [Move; 4] = [Move::Left, Move::Up, Move::Right, Move::Down];
 
 pub fn flood(field: &Field, boundary: &HashSet<Point>, start: Point) -> HashSet<Point> {
-    let m = field.m as i16;
-    let n = field.n as i16;
-
-    // result is the growing set of points describing the filled area
-    let mut result: HashSet<Point> = HashSet::new();
+    let boundary: Vec<Point> = boundary.iter().cloned().collect();
+    let mut blocked = field.cells.filled();
+    blocked.union_with(&BitBoard::from_points(field.m, field.n, &boundary));
+    flood_board(field, &blocked, start).points().into_iter().collect()
+}
 
-    let has_inside = |Point(i, j)| {
-        0 <= i && i < m && 0 <= j && j < n
-    };
-    let in_area = |p, result: &HashSet<Point>| {
-        has_inside(p)
-            && !result.contains(&p)
-            && !boundary.contains(&p)
-            && field.cells[p.0 as usize][p.1 as usize] == Cell::Empty
-    };
+/// the connected area of the cells not in `blocked`, containing `start`
+fn flood_board(field: &Field, blocked: &BitBoard, start: Point) -> BitBoard {
+    let mut result = BitBoard::new(field.m, field.n);
     // if the starting point on the boundary, return immediately
-    if !in_area(start, &result) {
+    if !start.is_inside(field.m, field.n) || blocked.contains(start) {
         return result;
     }
-    // if the starting point is somewhere inside, calculate the area
-    let mut queue: VecDeque<Point> = VecDeque::with_capacity(field.m + field.n);
-    queue.push_back(start);
-    while !queue.is_empty() {
-        let cur = queue.pop_front().unwrap();
-        result.insert(cur);
-        let mut candidates = NEIGHBORS.iter()
-            .map(|p| Point(cur.0 + p.0, cur.1 + p.1))
-            .filter(|p| in_area(*p, &result) && !queue.contains(p))
-            .collect();
-        queue.append(&mut candidates);
+    let mut labels = vec![0; field.m * field.n];
+    scanline_fill(field.m, field.n, blocked, &mut labels, field.cells.index_of(start), 1);
+    for (k, _) in labels.iter().enumerate().filter(|(_, &l)| l == 1) {
+        result.insert_index(k);
     }
     result
 }
 
-pub fn calculate_flood_area(field: &Field, body: &Vec<Point>) -> Vec<Point> {
-    let neighbors = vec![Point(0, -1), Point(-1, 0), Point(0, 1), Point(1, 0)];
-    let boundary: HashSet<Point> = body.iter().cloned().collect();
-    let mut areas: Vec<HashSet<Point>> = vec![];
+/// mark the connected area of the free cells around `start` with `label`, the cells
+/// are filled by the horizontal spans, so each cell is visited a constant number of times,
+/// `labels` must be 0 for the cells not filled yet, returns the size of the area
+fn scanline_fill(m: usize, n: usize, blocked: &BitBoard, labels: &mut [u32], start: usize, label: u32) -> usize {
+    let is_free = |k: usize, labels: &[u32]| labels[k] == 0 && !blocked.contains_index(k);
+    let mut size = 0;
+    let mut seeds = vec![start];
+    while let Some(seed) = seeds.pop() {
+        if !is_free(seed, labels) {
+            continue;
+        }
+        // extend the span to the left and to the right
+        let row = seed - seed % n;
+        let mut l = seed;
+        while l > row && is_free(l - 1, labels) {
+            l -= 1;
+        }
+        let mut r = seed;
+        while r + 1 < row + n && is_free(r + 1, labels) {
+            r += 1;
+        }
+        for label_k in &mut labels[l..=r] {
+            *label_k = label;
+        }
+        size += r - l + 1;
+        // seed the runs of the free cells above and below the span
+        let i = row / n;
+        for adj in [i.wrapping_sub(1), i + 1].iter().filter(|&&adj| adj < m) {
+            let from = adj * n + (l - row);
+            let mut in_run = false;
+            for k in from..=from + (r - l) {
+                let free = is_free(k, labels);
+                if free && !in_run {
+                    seeds.push(k);
+                }
+                in_run = free;
+            }
+        }
+    }
+    size
+}
 
-    let in_areas: Box<dyn Fn(Point, &Vec<HashSet<Point>>) -> bool> = Box::new(|p, areas| {
-        areas.iter().any(|ps| ps.contains(&p))
-    });
+/// The cells to be captured when the player closes the loop with `body`.
+/// The free cells are split into the areas, the captured one is the smallest area
+/// adjacent to the body (the first found if there are several), if there is only one
+/// such area, the player captures only the body.
+pub fn calculate_flood_area(field: &Field, body: &Vec<Point>) -> Vec<Point> {
+    let mut blocked = field.cells.filled();
+    blocked.union_with(&BitBoard::from_points(field.m, field.n, body));
+    // labels[k] is the number of the area the cell k belongs to, starting from 1
+    let mut labels = vec![0; field.m * field.n];
+    let mut sizes: Vec<usize> = vec![];
 
     for b in body.iter() {
         // search in the neighborhood of p empty areas
         // empty means not only empty surface but also free of players
-        let mut starts: Vec<Point> = neighbors.iter()
-            .map(|p| Point(b.0 + p.0, b.1 + p.1))
-            .filter(|p| has_inside(&field, *p)
-                && field.cells[p.0 as usize][p.1 as usize] == Cell::Empty
-                && !boundary.contains(p)
-                && !in_areas(*p, &areas))
-            .collect();
-        for sp in starts {
-            if !in_areas(sp, &areas) {
-                let flood = flood(&field, &boundary, sp);
-                areas.push(flood);
+        for &mv in &NEIGHBORS {
+            let sp = b.next(mv);
+            if sp.is_inside(field.m, field.n) {
+                let k = field.cells.index_of(sp);
+                if labels[k] == 0 && !blocked.contains_index(k) {
+                    let label = sizes.len() as u32 + 1;
+                    sizes.push(scanline_fill(field.m, field.n, &blocked, &mut labels, k, label));
+                }
             }
         }
     }
@@ -732,125 +1116,296 @@ pub fn calculate_flood_area(field: &Field, body: &Vec<Point>) -> Vec<Point> {
     // * a       *
     // *         *
     // D * * * * C
-    if areas.len() <= 1 {
-        areas.push(HashSet::new());
-    }
-    // seek for the area by the minimum size
-    let mut flooded: Vec<Point> = areas.iter()
-        .min_by(|s1, s2| s1.len().cmp(&s2.len())).unwrap()
-        .iter()
-        .map(|p| *p)
-        .collect_vec();
+    if sizes.len() <= 1 {
+        return body.clone();
+    }
+    // seek for the area by the minimum size, the first one if there are several
+    let label = sizes.iter().enumerate()
+        .min_by_key(|(_, &size)| size)
+        .map(|(l, _)| l as u32 + 1)
+        .unwrap();
+    let mut flooded: Vec<Point> = labels.iter().enumerate()
+        .filter(|(_, &l)| l == label)
+        .map(|(k, _)| Point((k / field.n) as i16, (k % field.n) as i16))
+        .collect();
     flooded.append(&mut body.clone());
     flooded
 }
 
 pub fn step(gs: &mut GameState, idx: usize, mv: Move) {
+    step_impl(gs, idx, mv, None, None)
+}
+
+/// the same as `step`, but pushes what happened to `events`
+pub fn step_with_events(gs: &mut GameState, idx: usize, mv: Move, events: &mut Vec<Event>) {
+    step_impl(gs, idx, mv, None, Some(events))
+}
+
+/// the same as `step`, but returns the record to revert it with `unstep`
+pub fn step_with_undo(gs: &mut GameState, idx: usize, mv: Move) -> Undo {
+    let mut undo = Undo {
+        cells: vec![],
+        bodies: vec![],
+        filled_count: gs.stats.filled_count,
+        head_to_head_count: gs.stats.head_to_head_count,
+        ouroboros_count: gs.stats.ouroboros_count,
+        bite_count: gs.stats.bite_count,
+        score: (idx, gs.stats.scores[idx]),
+    };
+    step_impl(gs, idx, mv, Some(&mut undo), None);
+    undo
+}
+
+/// revert the `step_with_undo`, the steps must be reverted in the reverse order
+pub fn unstep(gs: &mut GameState, undo: Undo) {
+    for (p, cell) in undo.cells.into_iter().rev() {
+        gs.field.cells.set(p, cell);
+    }
+    for change in undo.bodies.into_iter().rev() {
+        match change {
+            BodyChange::Pushed(k) => { gs.players[k].body_mut().pop(); }
+            BodyChange::Replaced(k, body) => *gs.players[k].body_mut() = body,
+        }
+    }
+    gs.stats.filled_count = undo.filled_count;
+    gs.stats.head_to_head_count = undo.head_to_head_count;
+    gs.stats.ouroboros_count = undo.ouroboros_count;
+    gs.stats.bite_count = undo.bite_count;
+    gs.stats.scores[undo.score.0] = undo.score.1;
+}
+
+/// replace the body of the player, remember the old one if needed
+fn replace_body(gs: &mut GameState, k: usize, body: Vec<Point>, undo: &mut Option<&mut Undo>) {
+    let old = std::mem::replace(gs.players[k].body_mut(), body);
+    if let Some(undo) = undo {
+        undo.bodies.push(BodyChange::Replaced(k, old));
+    }
+}
+
+fn emit(events: &mut Option<&mut Vec<Event>>, event: Event) {
+    if let Some(events) = events {
+        events.push(event);
+    }
+}
+
+fn step_impl(gs: &mut GameState, idx: usize, mv: Move, mut undo: Option<&mut Undo>, mut events: Option<&mut Vec<Event>>) {
     let index = idx;
     let np = gs.players.len();
 
-    let old_head = *gs.players[index].head().expect("Broken invariant");
+    let old_head = match gs.players[index].head() {
+        Some(&head) => head,
+        // the player is out of the game, see `respawn`
+        None => return,
+    };
     let new_head = calculate_head(&gs.field, old_head, mv);
     // let mut stats = &gs.stats;
 
     // the player hasn't effectively moved
     if old_head == new_head {
+        if mv != Move::Stop {
+            emit(&mut events, Event::Blocked { player: index });
+        }
         return;
     }
-    let old_cell = gs.field.cells[old_head.0 as usize][old_head.1 as usize];
-    let new_cell = gs.field.cells[new_head.0 as usize][new_head.1 as usize];
     // detect a collision
     let collision = (0..np).filter(|k| gs.players[*k].body().contains(&new_head)).next();
-    if new_head != old_head && collision.is_some() {
-        let coll_idx = collision.unwrap();
+    if let Some(coll_idx) = collision {
         let coll_head = *gs.players[coll_idx].head().expect("Broken invariant");
         if new_head == coll_head {
             // the player bumps with the other player's head
             gs.stats.head_to_head_count += 1;
+            emit(&mut events, Event::HeadToHead { a: index, b: coll_idx });
         } else if coll_idx == index {
             // the player eats itself
-            let respawn = calculate_respawn(gs, index).expect("Broken invariant");
-            gs.players[index].body_mut().clear();
-            gs.players[index].body_mut().push(respawn);
+            let at = respawn(gs, index, &mut undo);
             gs.stats.ouroboros_count += 1;
+            emit(&mut events, Event::Ouroboros { player: index, at: new_head });
+            emit_respawn(&mut events, index, at);
         } else {
             // the player `index` moves, and other player `coll_idx` dies,
             // if the current player was on the empty cell, its tail increases
             // otherwise it just moves to the next cell
-            let respawn = calculate_respawn(gs, coll_idx).expect("Broken invariant");
-            gs.players[coll_idx].body_mut().clear();
-            gs.players[coll_idx].body_mut().push(respawn);
+            let at = respawn(gs, coll_idx, &mut undo);
             gs.stats.bite_count += 1;
-            if old_cell == Cell::Empty {
-                gs.players[index].body_mut().push(new_head);
-            } else {
-                gs.players[index].body_mut().clear();
-                gs.players[index].body_mut().push(new_head);
+            emit(&mut events, Event::Bite { killer: index, victim: coll_idx, at: new_head });
+            emit_respawn(&mut events, coll_idx, at);
+            let captured = advance(gs, index, new_head, &mut undo);
+            emit_advance(&mut events, index, new_head, captured);
+        }
+    } else {
+        let captured = advance(gs, index, new_head, &mut undo);
+        emit_advance(&mut events, index, new_head, captured);
+    }
+}
+
+fn emit_respawn(events: &mut Option<&mut Vec<Event>>, player: usize, at: Option<Point>) {
+    if let Some(at) = at {
+        emit(events, Event::Respawn { player, at });
+    }
+}
+
+fn emit_advance(events: &mut Option<&mut Vec<Event>>, player: usize, to: Point, captured: u16) {
+    emit(events, Event::Moved { player, to });
+    if captured > 0 {
+        emit(events, Event::Captured { player, cells: captured });
+    }
+}
+
+/// Resolve the moves of all the players at once, the moves are chosen from the same snapshot.
+/// - the heads are solid during the tick: if several players enter the same cell,
+///   swap places or enter the cell with a head (even if that head is leaving it),
+///   they all stay, and each such pair counts as a head to head
+/// - the bites are checked against the bodies before the tick, the bitten player dies,
+///   if the players bite each other, both die
+/// - the players which survived do their moves in the index order, and the dead respawn after that
+pub fn step_simultaneous(gs: &mut GameState, moves: &[Move]) {
+    step_simultaneous_impl(gs, moves, None)
+}
+
+/// the same as `step_simultaneous`, but pushes what happened to `events`
+pub fn step_simultaneous_with_events(gs: &mut GameState, moves: &[Move], events: &mut Vec<Event>) {
+    step_simultaneous_impl(gs, moves, Some(events))
+}
+
+fn step_simultaneous_impl(gs: &mut GameState, moves: &[Move], mut events: Option<&mut Vec<Event>>) {
+    let np = gs.players.len();
+    debug_assert_eq!(np, moves.len());
+    // the players out of the game have no head and stay, see `respawn`
+    let heads: Vec<Option<Point>> = gs.players.iter().map(|p| p.head().cloned()).collect();
+    let targets: Vec<Option<Point>> = (0..np).map(|k| heads[k].map(|h| calculate_head(&gs.field, h, moves[k]))).collect();
+    let moving: Vec<bool> = (0..np).map(|k| targets[k] != heads[k]).collect();
+    for k in (0..np).filter(|&k| heads[k].is_some() && !moving[k] && moves[k] != Move::Stop) {
+        emit(&mut events, Event::Blocked { player: k });
+    }
+    // find the head to head conflicts, each pair counts once
+    let mut blocked = vec![false; np];
+    for a in 0..np {
+        for b in (a + 1)..np {
+            let same_target = moving[a] && moving[b] && targets[a] == targets[b];
+            let a_into_b = moving[a] && targets[a] == heads[b];
+            let b_into_a = moving[b] && targets[b] == heads[a];
+            if same_target || a_into_b || b_into_a {
+                gs.stats.head_to_head_count += 1;
+                emit(&mut events, Event::HeadToHead { a, b });
+                blocked[a] |= same_target || a_into_b;
+                blocked[b] |= same_target || b_into_a;
+            }
+        }
+    }
+    // the bites and the ouroboros by the bodies before the tick
+    let mut dead = vec![false; np];
+    let moves_to = |k: usize| targets[k].filter(|_| moving[k] && !blocked[k]).map(|p| (k, p));
+    for (k, target) in (0..np).filter_map(moves_to) {
+        let collision = (0..np).find(|&l| gs.players[l].body().contains(&target));
+        match collision {
+            Some(l) if l == k => {
+                gs.stats.ouroboros_count += 1;
+                emit(&mut events, Event::Ouroboros { player: k, at: target });
+                dead[k] = true;
+            }
+            Some(l) => {
+                gs.stats.bite_count += 1;
+                emit(&mut events, Event::Bite { killer: k, victim: l, at: target });
+                dead[l] = true;
             }
+            None => {}
         }
-    } else if new_head != old_head && old_cell != Cell::Empty {
+    }
+    for k in (0..np).filter(|&k| dead[k]) {
+        gs.players[k].body_mut().clear();
+    }
+    for (k, target) in (0..np).filter_map(moves_to).filter(|&(k, _)| !dead[k]) {
+        let captured = advance(gs, k, target, &mut None);
+        emit_advance(&mut events, k, target, captured);
+    }
+    for k in (0..np).filter(|&k| dead[k]) {
+        let at = respawn(gs, k, &mut None);
+        emit_respawn(&mut events, k, at);
+    }
+}
+
+/// move the player `index` to the free cell `new_head`, capture the area if the contour is closed,
+/// returns the number of the cells captured
+fn advance(gs: &mut GameState, index: usize, new_head: Point, undo: &mut Option<&mut Undo>) -> u16 {
+    let np = gs.players.len();
+    let old_head = *gs.players[index].head().expect("Broken invariant");
+    let old_cell = gs.field.cells[old_head.0 as usize][old_head.1 as usize];
+    let new_cell = gs.field.cells[new_head.0 as usize][new_head.1 as usize];
+    if old_cell != Cell::Empty {
         // we stay on the nonempty cell
         // single head, don't make the tail, just set the head
         // otherwise we should have made the contour from the previous step
         if gs.players[index].body().len() > 1 {
             panic!("Broken invariant");
         }
-        gs.players[index].body_mut().clear();
-        gs.players[index].body_mut().push(new_head);
-    } else if new_head != old_head && new_cell != Cell::Empty {
+        replace_body(gs, index, vec![new_head], undo);
+    } else if new_cell != Cell::Empty {
         // we step from empty to nonempty, calculate the contours
         // flood area now becomes owned by the current player
         // flood removes tails if any
         let flooded = calculate_flood_area(&gs.field, gs.players[index].body());
-        for p in &flooded {
-            let i = p.0 as usize;
-            let j = p.1 as usize;
-            gs.field.cells[i][j] = Cell::Owned(index as u8);
+        for &p in &flooded {
+            if let Some(undo) = undo {
+                undo.cells.push((p, gs.field.cells.get(p)));
+            }
+            gs.field.cells.set(p, Cell::Owned(index as u8));
         }
         for k in 0..np {
             if k == index {
-                gs.players[k].body_mut().clear();
-                gs.players[k].body_mut().push(new_head);
+                replace_body(gs, k, vec![new_head], undo);
             } else if gs.players[k].body().is_empty() {
-                panic!("Broken invariant");
+                // the player died this tick and waits for the respawn
+                continue;
             } else {
                 let head = *gs.players[k].head().expect("Broken invariant");
                 let cells = &gs.field.cells;
-                gs.players[k].body_mut().retain(|ref p| {
-                    // remove the points from the body if they are owned by someone else
-                    match cells[p.0 as usize][p.1 as usize] {
-                        Cell::Owned(_) => false,
-                        _ => true
+                // remove the points from the body if they are owned by someone else
+                let is_owned = |p: &Point| match cells[p.0 as usize][p.1 as usize] {
+                    Cell::Owned(_) => true,
+                    _ => false
+                };
+                if gs.players[k].body().iter().any(is_owned) {
+                    let mut body: Vec<Point> = gs.players[k].body().iter().filter(|p| !is_owned(p)).cloned().collect();
+                    // just in case
+                    if body.is_empty() {
+                        body.push(head);
                     }
-                });
-                // just in case
-                if gs.players[k].body().is_empty() {
-                    gs.players[k].body_mut().push(head);
+                    replace_body(gs, k, body, undo);
                 }
             }
         }
         // finally update statistics
         gs.stats.scores[index] += (&flooded).len() as u16;
         gs.stats.filled_count += (&flooded).len() as u16;
-    } else if new_head != old_head {
+        return flooded.len() as u16;
+    } else {
         // old_cell == Empty && new_cell == Empty (for sure)
         // we step into empty area, increase the tail
         // (head is the last element)
         gs.players[index].body_mut().push(new_head);
+        if let Some(undo) = undo {
+            undo.bodies.push(BodyChange::Pushed(index));
+        }
     }
+    0
+}
+
+/// returns where the player is respawned, none if all the border cells are taken,
+/// it happens only in the bots' simulations where the hidden border is taken for the empty cells,
+/// the player without the body is out of the game then
+fn respawn(gs: &mut GameState, dead_idx: usize, undo: &mut Option<&mut Undo>) -> Option<Point> {
+    let respawn = calculate_respawn(gs, dead_idx);
+    replace_body(gs, dead_idx, respawn.into_iter().collect(), undo);
+    respawn
 }
 
 fn calculate_head(field: &Field, old_p: Point, mv: Move) -> Point {
-    let Point(i, j) = old_p;
-    let (di, dj) = match mv {
-        Move::Right => (0, 1),
-        Move::Up    => (-1, 0),
-        Move::Left  => (0, -1),
-        Move::Down  => (1, 0),
-        Move::Stop  => (0, 0),
-    };
-    let new_p = Point(i + di, j + dj);
-    if has_inside(&field, new_p) { new_p } else { old_p }
+    let new_p = old_p.next(mv);
+    if new_p.is_inside(field.m, field.n) && field.cells[new_p.0 as usize][new_p.1 as usize] != Cell::Void {
+        new_p
+    } else {
+        old_p
+    }
 }
 
 pub fn calculate_respawn(gs: &GameState, dead_idx: usize) -> Option<Point> {
@@ -864,15 +1419,19 @@ pub fn calculate_respawn(gs: &GameState, dead_idx: usize) -> Option<Point> {
     let is_accessible = |p: Point| {
         let i = p.0 as usize;
         let j = p.1 as usize;
-        has_inside(&gs.field, p) && gs.field.cells[i][j] == Cell::Border && !others.contains(&p)
+        p.is_inside(gs.field.m, gs.field.n) && gs.field.cells[i][j] == Cell::Border && !others.contains(&p)
     };
 
     // find the closest to the origin nonempty cell
-    let origin = gs.origins[dead_idx];
+    find_nearest_rhomb(&gs.field, gs.origins[dead_idx], is_accessible)
+}
+
+/// search the point satisfying the predicate in the growing rhombs around `origin`
+pub fn find_nearest_rhomb(field: &Field, origin: Point, is_accessible: impl Fn(Point) -> bool) -> Option<Point> {
     if is_accessible(origin) {
         return Some(origin);
     }
-    for r in 1..((gs.field.m + gs.field.n) as i16) {
+    for r in 1..((field.m + field.n) as i16) {
         for k in 0..r {
             let p1 = Point(origin.0 - k, origin.1 + r - k);
             let p2 = Point(origin.0 - r + k, origin.1 - k);
@@ -887,18 +1446,39 @@ pub fn calculate_respawn(gs: &GameState, dead_idx: usize) -> Option<Point> {
     None
 }
 
-fn has_inside(field: &Field, p: Point) -> bool {
-    let Point(i, j) = p;
-    0 <= i && i < (field.m as i16) && 0 <= j && j < (field.n as i16)
+/// move the origins to the closest border cells, for the custom maps
+/// the default origins can be in a hole or in the middle of the field
+pub fn snap_origins(field: &Field, origins: &[Point]) -> Vec<Point> {
+    let mut result: Vec<Point> = Vec::with_capacity(origins.len());
+    for &origin in origins {
+        let is_accessible = |p: Point| {
+            p.is_inside(field.m, field.n)
+                && field.cells[p.0 as usize][p.1 as usize] == Cell::Border
+                && !result.contains(&p)
+        };
+        let snapped = find_nearest_rhomb(field, origin, is_accessible).unwrap_or(origin);
+        result.push(snapped);
+    }
+    result
 }
 
 pub fn create_match<T: AsRef<str>>(
     height: usize, width: usize, player_names: &[T], duration: u32, ratio: f32,
     random_seed: Option<u64>
+) -> Match {
+    create_match_on(create_default_field(height, width), player_names, duration, ratio, random_seed)
+}
+
+/// create the match on the custom field, see `Field::parse_string`
+pub fn create_match_on<T: AsRef<str>>(
+    field: Field, player_names: &[T], duration: u32, ratio: f32,
+    random_seed: Option<u64>
 ) -> Match {
     let np = player_names.len();
+    debug_assert!(np <= MAX_PLAYERS);
+    let height = field.m;
+    let width = field.n;
     let mut initializer_rng = random_seed.map(|seed| IsaacRng::seed_from_u64(seed));
-    let field = create_default_field(height, width);
     let perm0 = create_default_permutation(np);
     let origin_perm = match initializer_rng.borrow_mut() {
         Some(ref mut r) => copy_shuffled_permutation(&perm0, r),
@@ -909,23 +1489,10 @@ pub fn create_match<T: AsRef<str>>(
         None => perm0.clone()
     };
     // permute players if we have random generator
-    let origins = create_origins(height, width, &origin_perm);
+    let origins = snap_origins(&field, &create_origins(height, width, &origin_perm));
     let players = origins.iter().map(|&o| Player(vec![o])).collect();
     let player_names = player_names.iter().map(|s| s.as_ref().to_owned()).collect();
-    let mut filled_count = 0;
-    let mut scores = vec![0u16; np];
-    for i in 0..height {
-        for j in 0..width {
-            match field.cells[i][j] {
-                Cell::Empty => {}
-                Cell::Border => { filled_count += 1; }
-                Cell::Owned(k) => {
-                    filled_count += 1;
-                    scores[k as usize] = scores[k as usize] + 1;
-                }
-            }
-        }
-    }
+    let (filled_count, scores) = count_filled(&field.cells, np);
     let stats = Stats{
         iteration: 0,
         filled_count,
@@ -935,21 +1502,52 @@ pub fn create_match<T: AsRef<str>>(
         scores,
     };
     let game_state = GameState { field, players, player_names, origins, stats, reordering };
-    Match { duration, ratio, game_state, random_seed }
+    let timings = vec![Timing::default(); np];
+    Match {
+        duration, ratio, game_state, random_seed,
+        move_timeout: None, time_budget: None, timings, visibility: Visibility::Full,
+        rules: Rules::Sequential
+    }
 }
 
 pub fn run_match(the_match: &mut Match, bots: &mut [Box<dyn Bot>], logger: &dyn Fn(&GameState)) -> Replay {
-    let nb = bots.len();
+    run_match_with_events(the_match, bots, &|gs, _| logger(gs))
+}
+
+/// the same as `run_match`, but the logger also gets the events of the tick
+pub fn run_match_with_events(the_match: &mut Match, bots: &mut [Box<dyn Bot>], logger: &dyn Fn(&GameState, &[Event])) -> Replay {
+    run_match_impl(the_match, &mut InPlace(bots), logger)
+}
+
+/// the same as `run_match`, but the bots move on the worker threads, so the match stops waiting
+/// for the bot at `move_timeout` or when its `time_budget` is exhausted; the bots come back
+/// after the match, except the ones still busy
+pub fn run_timed_match(the_match: &mut Match, bots: &mut [Box<dyn Bot + Send>], logger: &dyn Fn(&GameState)) -> Replay {
+    run_timed_match_with_events(the_match, bots, &|gs, _| logger(gs))
+}
+
+/// the same as `run_timed_match`, but the logger also gets the events of the tick
+pub fn run_timed_match_with_events(the_match: &mut Match, bots: &mut [Box<dyn Bot + Send>], logger: &dyn Fn(&GameState, &[Event])) -> Replay {
+    run_match_impl(the_match, &mut Workers { bots, workers: vec![] }, logger)
+}
+
+fn run_match_impl(the_match: &mut Match, seats: &mut dyn Seats, logger: &dyn Fn(&GameState, &[Event])) -> Replay {
+    let nb = seats.len();
     debug_assert_eq!(nb, the_match.game_state.reordering.len());
     debug_assert_eq!(nb, the_match.game_state.players.len());
     debug_assert_eq!(nb, the_match.game_state.player_names.len());
     let mut all_moves: Vec<Vec<Move>> = Vec::with_capacity(the_match.duration as usize);
-    fn get_ratio(mat: &Match) -> f32 {
-        let m = mat.game_state.field.m as f32;
-        let n = mat.game_state.field.n as f32;
+    // the holes are never filled, so they don't count
+    let playable = the_match.game_state.field.playable_count() as f32;
+    let get_ratio = |mat: &Match| {
         let fc = mat.game_state.stats.filled_count as f32;
-        fc / (m * n)
-    }
+        fc / playable
+    };
+    let map = if the_match.game_state.field == create_default_field(the_match.game_state.field.m, the_match.game_state.field.n) {
+        None
+    } else {
+        Some(the_match.game_state.field.clone())
+    };
     // random generator will supply seeds for bots
     let mut rng = the_match.random_seed
         .map(|seed| IsaacRng::seed_from_u64(seed))
@@ -966,13 +1564,20 @@ pub fn run_match(the_match: &mut Match, bots: &mut [Box<dyn Bot>], logger: &dyn
         };
         pgss.push(pgs);
     }
+    the_match.timings = vec![Timing::default(); nb];
     for k in 0..nb {
         let idx = the_match.game_state.reordering[k] as usize;
         let seed: u64 = random_seed_gen();
         let mut cgs = &mut pgss[idx];
         make_game_state_view(cgs, &the_match.game_state, idx);
-        bots[idx].reset(cgs, idx, seed);
+        apply_visibility(cgs, the_match.visibility);
+        let bot = seats.bot(idx);
+        if let Some(timeout) = the_match.move_timeout {
+            bot.set_move_timeout(timeout);
+        }
+        bot.reset(cgs, idx, seed);
     }
+    seats.start();
     for tick in 0..the_match.duration {
         // if the cells has filled enough, do finish
         if get_ratio(the_match) >= the_match.ratio {
@@ -982,20 +1587,41 @@ pub fn run_match(the_match: &mut Match, bots: &mut [Box<dyn Bot>], logger: &dyn
         // tick + 1 because we want last iteration == allMoves.size
         the_match.game_state.stats.iteration = tick + 1;
         let mut moves = vec![Move::Stop; nb];
+        let mut events = vec![];
         // enumerate all the bots, move them
         for k in 0..nb {
             let idx = the_match.game_state.reordering[k] as usize;
             let mut cgs = &mut pgss[idx];
             make_game_state_view(cgs, &the_match.game_state, idx);
-            let m = bots[idx].do_move(cgs);
-            step(&mut the_match.game_state, idx, m);
+            apply_visibility(cgs, the_match.visibility);
+            // the forfeited bot is not asked anymore
+            let m = if the_match.timings[idx].forfeited {
+                Move::Stop
+            } else {
+                let timing = &mut the_match.timings[idx];
+                let left = the_match.time_budget.map(|budget| budget.checked_sub(timing.total).unwrap_or_default());
+                let deadline = match (the_match.move_timeout, left) {
+                    (Some(timeout), Some(left)) => Some(timeout.min(left)),
+                    (timeout, left) => timeout.or(left),
+                };
+                let (m, elapsed) = seats.do_move(idx, cgs, deadline);
+                let accepted = account_time(timing, elapsed, m.is_none(), the_match.move_timeout, the_match.time_budget);
+                m.filter(|_| accepted).unwrap_or(Move::Stop)
+            };
+            if the_match.rules == Rules::Sequential {
+                step_with_events(&mut the_match.game_state, idx, m, &mut events);
+            }
             moves[idx] = m;
             // is it better to do here?
             // logger(&the_match.game_state);
         }
-        logger(&the_match.game_state);
+        if the_match.rules == Rules::Simultaneous {
+            step_simultaneous_with_events(&mut the_match.game_state, &moves, &mut events);
+        }
+        logger(&the_match.game_state, &events);
         all_moves.push(moves);
     }
+    seats.finish();
     Replay {
         height: the_match.game_state.field.m,
         width: the_match.game_state.field.n,
@@ -1003,33 +1629,240 @@ pub fn run_match(the_match: &mut Match, bots: &mut [Box<dyn Bot>], logger: &dyn
         ratio: the_match.ratio,
         player_names: the_match.game_state.player_names.clone(),
         moves: all_moves,
-        random_seed: the_match.random_seed
+        random_seed: the_match.random_seed,
+        rules: the_match.rules,
+        map,
     }
 }
 
-/// returns the final game state after the replay run
+/// updates the timing of the bot, `late` is true if the worker gave up waiting for the move,
+/// returns false if the move must be discarded, the move can be both late and the last one
+fn account_time(timing: &mut Timing, elapsed: Duration, late: bool,
+                move_timeout: Option<Duration>, time_budget: Option<Duration>) -> bool {
+    timing.total += elapsed;
+    timing.max = timing.max.max(elapsed);
+    let overrun = late || move_timeout.map(|timeout| elapsed > timeout).unwrap_or(false);
+    if overrun {
+        timing.overruns += 1;
+    }
+    // the worker waits exactly until the budget is exhausted
+    if let Some(budget) = time_budget {
+        if timing.total > budget || (late && timing.total >= budget) {
+            timing.forfeited = true;
+        }
+    }
+    !overrun && !timing.forfeited
+}
+
+/// How the match asks the bots for the moves: right on its thread or on the workers
+trait Seats {
+    fn len(&self) -> usize;
+    fn bot(&mut self, idx: usize) -> &mut dyn Bot;
+    /// called when all the bots are reset
+    fn start(&mut self) {}
+    /// the move if it's made within `deadline`, and the time the bot has spent
+    fn do_move(&mut self, idx: usize, gsv: &GameStateView, deadline: Option<Duration>) -> (Option<Move>, Duration);
+    fn finish(&mut self) {}
+}
+
+/// the bots move on the match's thread, the move is always made, however late it is
+struct InPlace<'a>(&'a mut [Box<dyn Bot>]);
+
+impl Seats for InPlace<'_> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn bot(&mut self, idx: usize) -> &mut dyn Bot {
+        &mut *self.0[idx]
+    }
+
+    fn do_move(&mut self, idx: usize, gsv: &GameStateView, _deadline: Option<Duration>) -> (Option<Move>, Duration) {
+        let started = Instant::now();
+        let m = self.0[idx].do_move(gsv);
+        (Some(m), started.elapsed())
+    }
+}
+
+/// the bots are moved to the workers on `start` and come back on `finish`
+struct Workers<'a> {
+    bots: &'a mut [Box<dyn Bot + Send>],
+    workers: Vec<Worker>,
+}
+
+impl Seats for Workers<'_> {
+    fn len(&self) -> usize {
+        self.bots.len()
+    }
+
+    fn bot(&mut self, idx: usize) -> &mut dyn Bot {
+        &mut *self.bots[idx]
+    }
+
+    fn start(&mut self) {
+        self.workers = self.bots.iter_mut()
+            .map(|bot| Worker::spawn(std::mem::replace(bot, Box::new(LostBot))))
+            .collect();
+    }
+
+    fn do_move(&mut self, idx: usize, gsv: &GameStateView, deadline: Option<Duration>) -> (Option<Move>, Duration) {
+        self.workers[idx].do_move(gsv, deadline)
+    }
+
+    /// the ones still busy are lost
+    fn finish(&mut self) {
+        for (bot, worker) in self.bots.iter_mut().zip(self.workers.drain(..)) {
+            if let Some(returned) = worker.finish() {
+                *bot = returned;
+            }
+        }
+    }
+}
+
+/// The bot moving on its own thread. When the worker gives up waiting for the move,
+/// the bot is busy until the late answer comes, the moves asked meanwhile are missed.
+struct Worker {
+    views: Sender<GameStateView>,
+    answers: Receiver<(Move, Duration)>,
+    handle: JoinHandle<Box<dyn Bot + Send>>,
+    /// when the late move was asked and how much of its time is accounted
+    late: Option<(Instant, Duration)>,
+}
+
+impl Worker {
+    fn spawn(mut bot: Box<dyn Bot + Send>) -> Worker {
+        let (views, requests) = channel::<GameStateView>();
+        let (replies, answers) = channel();
+        let handle = thread::spawn(move || {
+            for gsv in requests {
+                let started = Instant::now();
+                let m = bot.do_move(&gsv);
+                if replies.send((m, started.elapsed())).is_err() {
+                    break;
+                }
+            }
+            bot
+        });
+        Worker { views, answers, handle, late: None }
+    }
+
+    /// the move if it comes within `deadline`, and the time the bot has spent
+    /// since the last call, the rest of the late move included
+    fn do_move(&mut self, gsv: &GameStateView, deadline: Option<Duration>) -> (Option<Move>, Duration) {
+        let mut spent = Duration::default();
+        if let Some((asked, accounted)) = self.late {
+            match self.answers.try_recv() {
+                Ok((_, elapsed)) => spent = elapsed.checked_sub(accounted).unwrap_or_default(),
+                Err(TryRecvError::Empty) => {
+                    let elapsed = asked.elapsed();
+                    self.late = Some((asked, elapsed));
+                    return (None, elapsed - accounted);
+                }
+                // the bot has panicked
+                Err(TryRecvError::Disconnected) => return (None, spent),
+            }
+            self.late = None;
+        }
+        let asked = Instant::now();
+        if self.views.send(gsv.clone()).is_err() {
+            return (None, spent);
+        }
+        let answer = match deadline {
+            Some(deadline) => self.answers.recv_timeout(deadline).ok(),
+            None => self.answers.recv().ok(),
+        };
+        match answer {
+            Some((m, elapsed)) => (Some(m), spent + elapsed),
+            None => {
+                let elapsed = asked.elapsed();
+                self.late = Some((asked, elapsed));
+                (None, spent + elapsed)
+            }
+        }
+    }
+
+    /// the bot back, none if it's still busy or has panicked
+    fn finish(self) -> Option<Box<dyn Bot + Send>> {
+        drop(self.views);
+        if self.late.is_some() && self.answers.try_recv().is_err() {
+            return None;
+        }
+        self.handle.join().ok()
+    }
+}
+
+/// stands in place of the bot lost in the worker
+struct LostBot;
+
+impl Bot for LostBot {
+    fn reset(&mut self, _gs: &GameStateView, _idx: usize, _seed: u64) {}
+
+    fn do_move(&mut self, _gs: &GameStateView) -> Move {
+        Move::Stop
+    }
+}
+
+/// returns the final game state after the replay run,
+/// the logger is called after every player's move, or once per tick for the simultaneous rules
 pub fn run_replay(replay: &Replay, logger: &dyn Fn(&GameState)) -> GameState {
-    let mut gs: GameState = create_match(
-        replay.height,
-        replay.width,
-        &replay.player_names,
-        replay.duration,
-        replay.ratio,
-        replay.random_seed
-    ).game_state;
+    run_replay_with_events(replay, &|gs, _| logger(gs))
+}
+
+/// the same as `run_replay`, but the logger also gets the events of the move or the tick
+pub fn run_replay_with_events(replay: &Replay, logger: &dyn Fn(&GameState, &[Event])) -> GameState {
+    let mut gs = replay_start(replay);
+    let mut events = vec![];
     for tick in 0..(replay.moves.len()) {
         gs.stats.iteration = (tick + 1) as u32;
+        if replay.rules == Rules::Simultaneous {
+            events.clear();
+            step_simultaneous_with_events(&mut gs, &replay.moves[tick], &mut events);
+            logger(&gs, &events);
+            continue;
+        }
         let np = gs.players.len();
         for k in 0..np {
             let idx = gs.reordering[k] as usize;
             let m = replay.moves[tick][idx];
-            step(&mut gs, idx, m);
-            logger(&gs);
+            events.clear();
+            step_with_events(&mut gs, idx, m, &mut events);
+            logger(&gs, &events);
         }
     }
     gs
 }
 
+/// the state before the first tick and the states after each tick of the replay
+pub fn replay_frames(replay: &Replay) -> Vec<GameState> {
+    // the logger is called after every player's move, take only the whole ticks
+    let per_tick = match replay.rules {
+        Rules::Sequential => replay.player_names.len().max(1),
+        Rules::Simultaneous => 1,
+    };
+    let frames = RefCell::new(vec![replay_start(replay)]);
+    let calls = RefCell::new(0);
+    run_replay(replay, &|gs| {
+        let mut calls = calls.borrow_mut();
+        *calls += 1;
+        if *calls == per_tick {
+            *calls = 0;
+            frames.borrow_mut().push(gs.clone());
+        }
+    });
+    frames.into_inner()
+}
+
+pub(crate) fn replay_start(replay: &Replay) -> GameState {
+    let field = replay.map.clone().unwrap_or_else(|| create_default_field(replay.height, replay.width));
+    create_match_on(
+        field,
+        &replay.player_names,
+        replay.duration,
+        replay.ratio,
+        replay.random_seed
+    ).game_state
+}
+
 // reset to default color is \e[0m
 // https://misc.flogisoft.com/bash/tip_colors_and_formatting
 // must be at least 10 items
@@ -1056,27 +1889,56 @@ const STYLES: &'static [&'static str] = &[
     "magenta.bold",
 ];
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum Symbol {
-    Digit(usize),
+    Owner(usize),
     Letter,
+    Highlight,
     Other
 }
 
+/// the owners after `STYLES` get the colors from the 256 color palette,
+/// the components are from 1 to 5, so the colors are not too dark
+fn paint_256(piece: &str, owner: usize, force: bool) -> String {
+    if !force && !console::colors_enabled() {
+        return piece.to_string();
+    }
+    // 37 is coprime with 125, so the first 125 owners get the different colors
+    let k = (owner * 37) % 125;
+    let (r, g, b) = (1 + k / 25, 1 + k / 5 % 5, 1 + k % 5);
+    format!("\x1B[38;5;{}m{}\x1B[0m", 16 + 36 * r + 6 * g + b, piece)
+}
+
 pub fn prettify_game_state(gs: &GameState, rewind: bool, use_colors: bool) -> String {
+    prettify_impl(gs, rewind, use_colors, false, &[])
+}
+
+/// `force_colors` paints even if the output is not a terminal, e.g. for the recordings,
+/// the owned cells in `highlight` are shown in the reverse video, or as `+` without colors.
+/// Up to 10 players the owners are `0`...`9`, the heads `A`...`J` and the tails `a`...`j`,
+/// with more players the cell is the owner char followed by `+` for the head, `-` for the tail
+pub(crate) fn prettify_impl(gs: &GameState, rewind: bool, use_colors: bool, force_colors: bool, highlight: &[Point]) -> String {
     let m = gs.field.m;
     let n = gs.field.n;
     let np = gs.players.len();
+    let wide = np > NARROW_PLAYERS;
+    let mut marks = vec![vec![b' '; n]; m];
     let capacity = if use_colors { m * (8 * n + 1) + 2 } else { m * 2 * (m + n) + 10 * np + 30 };
     let mut result = String::with_capacity(capacity);
-    let mut layer0 = vec![vec![' ' as u8; n]; m];
+    let mut layer0 = vec![vec![b' '; n]; m];
+    let mut symbols = vec![vec![Symbol::Other; n]; m];
 
     for i in 0..m {
         for j in 0..n {
             match gs.field.cells[i][j] {
-                Cell::Empty => layer0[i][j] = '.' as u8,
-                Cell::Border => layer0[i][j] = '*' as u8,
-                Cell::Owned(k) => layer0[i][j] = ('0' as u8) + k,
+                Cell::Empty => layer0[i][j] = b'.',
+                Cell::Border => layer0[i][j] = b'*',
+                Cell::Owned(k) => {
+                    layer0[i][j] = owner_char(k);
+                    symbols[i][j] = Symbol::Owner(k as usize);
+                }
+                Cell::Hidden => layer0[i][j] = b'?',
+                Cell::Void => layer0[i][j] = b' ',
             };
         }
     }
@@ -1085,18 +1947,33 @@ pub fn prettify_game_state(gs: &GameState, rewind: bool, use_colors: bool) -> St
         for l in 0..player.len() {
             let i = player[l].0 as usize;
             let j = player[l].1 as usize;
-            if l == player.len() - 1 {
-                layer0[i][j] = ('A' as u8) + (k as u8);
-            } else {
-                layer0[i][j] = ('a' as u8) + (k as u8);
+            let head = l == player.len() - 1;
+            layer0[i][j] = match (wide, head) {
+                (false, true) => b'A' + k as u8,
+                (false, false) => b'a' + k as u8,
+                (true, _) => owner_char(k as u8),
+            };
+            if wide {
+                marks[i][j] = if head { b'+' } else { b'-' };
+            }
+            symbols[i][j] = if head { Symbol::Letter } else { Symbol::Other };
+        }
+    }
+    for p in highlight.iter().filter(|p| p.is_inside(m, n)) {
+        let (i, j) = (p.0 as usize, p.1 as usize);
+        if let Symbol::Owner(_) = symbols[i][j] {
+            symbols[i][j] = Symbol::Highlight;
+            if !use_colors {
+                layer0[i][j] = b'+';
             }
         }
     }
+    let cw = if wide { 3 } else { 2 };
     // now build the result string
     for k in 0..np {
-        let mut s = String::with_capacity(n * 2);
+        let mut s = String::with_capacity(n * cw);
         s.push_str(&format!("{}: {}", gs.player_names[k], gs.stats.scores[k]));
-        let rest_len = n * 2 - 1 - s.len();
+        let rest_len = (n * cw).saturating_sub(1 + s.len());
         for l in 0..rest_len {
             s.push(' ');
         }
@@ -1105,38 +1982,39 @@ pub fn prettify_game_state(gs: &GameState, rewind: bool, use_colors: bool) -> St
     }
     // write the matrix
     if use_colors {
-        let styles = STYLES.iter().map(|s| Style::from_dotted_str(s)).collect::<Vec<Style>>();
+        // otherwise the terminal is detected
+        let force = |style: Style| if force_colors { style.force_styling(true) } else { style };
+        let styles = STYLES.iter().map(|s| force(Style::from_dotted_str(s))).collect::<Vec<Style>>();
         let empty_style = Style::new();
-        let white_bold_style = Style::from_dotted_str("white.bold");
+        let white_bold_style = force(Style::from_dotted_str("white.bold"));
+        let highlight_style = force(Style::new().reverse());
+        let paint = |piece: &str, symbol: &Symbol| match *symbol {
+            Symbol::Owner(d) if d < styles.len() => styles[d].apply_to(piece).to_string(),
+            Symbol::Owner(d) => paint_256(piece, d, force_colors),
+            Symbol::Letter => white_bold_style.apply_to(piece).to_string(),
+            Symbol::Highlight => highlight_style.apply_to(piece).to_string(),
+            Symbol::Other => empty_style.apply_to(piece).to_string(),
+        };
         let mut current_piece: String = String::with_capacity(n * 4);
-        let mut current_style = &empty_style;
         let mut prev_symbol = Symbol::Other;
         for i in 0..m {
             for j in 0..n {
                 if j != 0 {
                     current_piece.push(' ');
                 }
-                let cur_symbol = if layer0[i][j].is_ascii_digit() {
-                    Symbol::Digit((layer0[i][j] - ('0' as u8)) as usize)
-                } else if layer0[i][j].is_ascii_uppercase() {
-                    Symbol::Letter
-                } else {
-                    Symbol::Other
-                };
+                let cur_symbol = symbols[i][j];
                 if cur_symbol != prev_symbol {
-                    result.push_str(&current_style.apply_to(&current_piece).to_string());
+                    result.push_str(&paint(&current_piece, &prev_symbol));
                     current_piece.clear();
                 }
-                match cur_symbol {
-                    Symbol::Digit(d) => current_style = &styles[d],
-                    Symbol::Letter => current_style = &white_bold_style,
-                    Symbol::Other => current_style = &empty_style,
-                }
                 current_piece.push(layer0[i][j] as char);
+                if wide {
+                    current_piece.push(marks[i][j] as char);
+                }
                 prev_symbol = cur_symbol;
             }
             current_piece.push('\n');
-            result.push_str(&current_style.apply_to(&current_piece).to_string());
+            result.push_str(&paint(&current_piece, &prev_symbol));
             current_piece.clear();
         }
     } else {
@@ -1146,6 +2024,9 @@ pub fn prettify_game_state(gs: &GameState, rewind: bool, use_colors: bool) -> St
                 if j != 0 { result.push(' '); }
                 let c = layer0[i][j] as char;
                 result.push(c);
+                if wide {
+                    result.push(marks[i][j] as char);
+                }
             }
             result.push('\n');
         }
@@ -0,0 +1,350 @@
+//! Rendering for sharing the matches: a game state becomes a standalone SVG image,
+//! a replay becomes a self-contained HTML page with the player, no external resources.
+//!
+//! The colors of the players follow the terminal ones of `prettify_game_state`.
+//!
+//! A replay also becomes an asciinema recording (asciicast v2), the frames are timed
+//! by the ticks, not by the wall clock.
+
+use crate::model::*;
+use std::fmt::Write;
+use std::io;
+use std::time::Duration;
+
+/// the size of the cell in pixels
+pub const CELL_SIZE: usize = 12;
+
+/// the colors of the terminal styles: the plain, the dim and the bold ones
+const COLORS: &[&str] = &[
+    "#cc3333", "#33aa33", "#ccaa11", "#3355cc", "#22aabb", "#aa33aa",
+    "#882222", "#226622", "#887711", "#223388", "#117777", "#772277",
+    "#ff5555", "#55ee55", "#ffdd33", "#5577ff", "#44ddee", "#ee55ee",
+];
+/// the components of the 256 colors palette
+const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+const EMPTY_COLOR: &str = "#f4f4f4";
+const BORDER_COLOR: &str = "#888888";
+const HIDDEN_COLOR: &str = "#cccccc";
+const VOID_COLOR: &str = "#222222";
+
+/// the color of the player `k` as `#rrggbb`, the same as in the terminal
+pub fn player_color(k: usize) -> String {
+    if k < COLORS.len() {
+        return COLORS[k].to_string();
+    }
+    // see `paint_256`
+    let c = (k * 37) % 125;
+    let (r, g, b) = (1 + c / 25, 1 + c / 5 % 5, 1 + c % 5);
+    format!("#{:02x}{:02x}{:02x}", LEVELS[r], LEVELS[g], LEVELS[b])
+}
+
+/// The standalone SVG image of the state: the owned cells are pale, the tails are
+/// the smaller squares, the heads are the full ones with the letters, the scores are below.
+pub fn render_svg(gs: &GameState) -> String {
+    let (m, n) = (gs.field.m, gs.field.n);
+    let np = gs.players.len();
+    let (width, height) = (n * CELL_SIZE, (m + np + 1) * CELL_SIZE);
+    let mut svg = String::with_capacity(m * n * 64);
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="monospace" font-size="{fs}">"#,
+        w = width, h = height, fs = CELL_SIZE - 2,
+    );
+    let _ = writeln!(svg, r#"<rect width="{}" height="{}" fill="{}"/>"#, width, m * CELL_SIZE, EMPTY_COLOR);
+    for i in 0..m {
+        for j in 0..n {
+            let (fill, opacity) = match gs.field.cells[i][j] {
+                Cell::Empty => continue,
+                Cell::Border => (BORDER_COLOR.to_string(), 1.0),
+                Cell::Owned(k) => (player_color(k as usize), 0.45),
+                Cell::Hidden => (HIDDEN_COLOR.to_string(), 1.0),
+                Cell::Void => (VOID_COLOR.to_string(), 1.0),
+            };
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{s}" height="{s}" fill="{}" fill-opacity="{}"/>"#,
+                j * CELL_SIZE, i * CELL_SIZE, fill, opacity, s = CELL_SIZE,
+            );
+        }
+    }
+    for (k, player) in gs.players.iter().enumerate() {
+        let color = player_color(k);
+        let body = player.body();
+        for (l, p) in body.iter().enumerate() {
+            let (x, y) = (p.1 as usize * CELL_SIZE, p.0 as usize * CELL_SIZE);
+            if l + 1 < body.len() {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{s}" height="{s}" fill="{}"/>"#,
+                    x + CELL_SIZE / 4, y + CELL_SIZE / 4, color, s = CELL_SIZE / 2,
+                );
+            } else {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{s}" height="{s}" fill="{}" stroke="black"/>"#,
+                    x, y, color, s = CELL_SIZE,
+                );
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" text-anchor="middle" fill="white">{}</text>"#,
+                    x + CELL_SIZE / 2, y + CELL_SIZE - 2, head_label(k),
+                );
+            }
+        }
+    }
+    for k in 0..np {
+        let _ = writeln!(
+            svg,
+            r#"<text x="0" y="{}" fill="{}">{}: {}</text>"#,
+            (m + k + 1) * CELL_SIZE - 2, player_color(k), escape_html(&gs.player_names[k]), gs.stats.scores[k],
+        );
+    }
+    let _ = writeln!(svg, r#"<text x="0" y="{}">iteration: {}</text>"#, (m + np + 1) * CELL_SIZE - 2, gs.stats.iteration);
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// The self-contained HTML page playing the replay: the field, the timeline slider,
+/// play/pause, the scores and the chart of the scores over the ticks.
+pub fn render_html(replay: &Replay) -> String {
+    let frames = replay_frames(replay);
+    let np = replay.player_names.len();
+    let (m, n) = frames.first().map(|gs| (gs.field.m, gs.field.n)).unwrap_or((0, 0));
+    let mut data = String::with_capacity(frames.len() * (m * n + 64 * np));
+    let _ = write!(data, r#"{{"m":{},"n":{},"cell":{},"names":["#, m, n, CELL_SIZE);
+    data.push_str(&replay.player_names.iter().map(|s| json_string(s)).collect::<Vec<_>>().join(","));
+    data.push_str(r#"],"colors":["#);
+    data.push_str(&(0..np).map(|k| json_string(&player_color(k))).collect::<Vec<_>>().join(","));
+    data.push_str(r#"],"letters":["#);
+    data.push_str(&(0..np).map(|k| json_string(&head_label(k))).collect::<Vec<_>>().join(","));
+    data.push_str(r#"],"frames":["#);
+    for (t, gs) in frames.iter().enumerate() {
+        if t > 0 {
+            data.push(',');
+        }
+        let cells: String = gs.field.cells.as_slice().iter().map(|&cell| match cell {
+            Cell::Empty => '.',
+            Cell::Border => '*',
+            Cell::Owned(k) => OWNER_CHARS[k as usize] as char,
+            Cell::Hidden => '?',
+            Cell::Void => '#',
+        }).collect();
+        let bodies: Vec<String> = gs.players.iter().map(|p| {
+            let points: Vec<String> = p.body().iter().map(|p| format!("[{},{}]", p.0, p.1)).collect();
+            format!("[{}]", points.join(","))
+        }).collect();
+        let scores: Vec<String> = gs.stats.scores.iter().map(|s| s.to_string()).collect();
+        let _ = write!(
+            data,
+            r#"{{"cells":{},"bodies":[{}],"scores":[{}]}}"#,
+            json_string(&cells), bodies.join(","), scores.join(","),
+        );
+    }
+    data.push_str("]}");
+    let chart = score_chart(&frames, np);
+    HTML_TEMPLATE
+        .replace("{{OWNER_CHARS}}", &json_string(std::str::from_utf8(OWNER_CHARS).unwrap_or("")))
+        .replace("{{CHART}}", &chart)
+        .replace("{{DATA}}", &data)
+}
+
+/// Write the asciicast v2 recording of the replay: the header line, then the frame per tick
+/// `delay` apart, the first one is the state before the first tick. The live terminal output
+/// rewinds the cursor up by the lines of the frame, here each frame moves the cursor home
+/// instead, so the recording doesn't depend on the size of the player's terminal.
+pub fn write_asciicast<W: io::Write>(out: &mut W, replay: &Replay, delay: Duration, use_colors: bool) -> io::Result<()> {
+    let frames = replay_frames(replay);
+    let np = replay.player_names.len();
+    let (m, n) = frames.first().map(|gs| (gs.field.m, gs.field.n)).unwrap_or((0, 0));
+    // the lines of the names are padded to the width of the field, see `prettify_game_state`
+    let name_width = replay.player_names.iter().map(|s| s.chars().count() + ": 65535".len()).max().unwrap_or(0);
+    let cw = if replay.player_names.len() > NARROW_PLAYERS { 3 } else { 2 };
+    let width = (cw * n).saturating_sub(1).max(name_width);
+    // and the cursor is on the line after the frame
+    let height = np + m + 2;
+    writeln!(
+        out,
+        r#"{{"version":2,"width":{},"height":{},"title":{}}}"#,
+        width, height, json_string(&replay.player_names.join(" vs ")),
+    )?;
+    for (t, gs) in frames.iter().enumerate() {
+        let mut frame = String::from(if t == 0 { "\x1B[2J\x1B[H" } else { "\x1B[H" });
+        // the recordings are of the terminal in the raw mode
+        frame.push_str(&prettify_impl(gs, false, use_colors, true, &[]).replace('\n', "\r\n"));
+        let time = delay.as_secs_f64() * t as f64;
+        writeln!(out, r#"[{:.6}, "o", {}]"#, time, json_string(&frame))?;
+    }
+    Ok(())
+}
+
+/// the SVG chart of the scores, `x` is the tick, the marker of the current tick is moved by the page
+fn score_chart(frames: &[GameState], np: usize) -> String {
+    let (width, height) = (600.0, 160.0);
+    let ticks = frames.len().saturating_sub(1).max(1) as f64;
+    let max_score = frames.iter()
+        .flat_map(|gs| gs.stats.scores.iter().cloned())
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let mut chart = format!(r#"<svg id="chart" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#, w = width, h = height);
+    let _ = write!(chart, r##"<rect width="{}" height="{}" fill="#fafafa" stroke="#ccc"/>"##, width, height);
+    for k in 0..np {
+        let points: Vec<String> = frames.iter().enumerate().map(|(t, gs)| {
+            let x = t as f64 / ticks * width;
+            let y = height - f64::from(gs.stats.scores[k]) / max_score * (height - 4.0);
+            format!("{:.1},{:.1}", x, y)
+        }).collect();
+        let _ = write!(
+            chart,
+            r#"<polyline fill="none" stroke="{}" stroke-width="1.5" points="{}"/>"#,
+            player_color(k), points.join(" "),
+        );
+    }
+    let _ = write!(chart, r#"<line id="marker" x1="0" y1="0" x2="0" y2="{}" stroke="black"/></svg>"#, height);
+    chart
+}
+
+/// the letter of the player, the players after `Z` are shown by their numbers
+fn head_label(k: usize) -> String {
+    if k < 26 { ((b'A' + k as u8) as char).to_string() } else { k.to_string() }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// the JSON string literal, safe to embed into `<script>`
+fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '<' => result.push_str("\\u003c"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(result, "\\u{:04x}", c as u32);
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+const HTML_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>xcg replay</title>
+<style>
+body { font-family: monospace; margin: 16px; }
+#controls { margin: 8px 0; }
+#timeline { width: 480px; vertical-align: middle; }
+#scores div { margin: 2px 0; }
+</style>
+</head>
+<body>
+<canvas id="field"></canvas>
+<div id="controls">
+<button id="play">play</button>
+<input id="timeline" type="range" min="0" value="0">
+<span id="tick"></span>
+</div>
+<div id="scores"></div>
+{{CHART}}
+<script>
+const DATA = {{DATA}};
+const OWNERS = {{OWNER_CHARS}};
+const COLORS = { '.': '#f4f4f4', '*': '#888888', '?': '#cccccc', '#': '#222222' };
+const canvas = document.getElementById('field');
+const ctx = canvas.getContext('2d');
+const timeline = document.getElementById('timeline');
+const button = document.getElementById('play');
+const s = DATA.cell;
+canvas.width = DATA.n * s;
+canvas.height = DATA.m * s;
+timeline.max = DATA.frames.length - 1;
+let current = 0;
+let timer = null;
+
+function draw(t) {
+  current = t;
+  const frame = DATA.frames[t];
+  for (let i = 0; i < DATA.m; i++) {
+    for (let j = 0; j < DATA.n; j++) {
+      const ch = frame.cells[i * DATA.n + j];
+      ctx.globalAlpha = 1.0;
+      ctx.fillStyle = COLORS['.'];
+      ctx.fillRect(j * s, i * s, s, s);
+      if (ch in COLORS) {
+        ctx.fillStyle = COLORS[ch];
+      } else {
+        ctx.fillStyle = DATA.colors[OWNERS.indexOf(ch)];
+        ctx.globalAlpha = 0.45;
+      }
+      ctx.fillRect(j * s, i * s, s, s);
+    }
+  }
+  ctx.globalAlpha = 1.0;
+  ctx.font = (s - 2) + 'px monospace';
+  ctx.textAlign = 'center';
+  frame.bodies.forEach((body, k) => {
+    body.forEach(([i, j], l) => {
+      ctx.fillStyle = DATA.colors[k];
+      if (l + 1 < body.length) {
+        ctx.fillRect(j * s + s / 4, i * s + s / 4, s / 2, s / 2);
+      } else {
+        ctx.fillRect(j * s, i * s, s, s);
+        ctx.strokeRect(j * s + 0.5, i * s + 0.5, s - 1, s - 1);
+        ctx.fillStyle = 'white';
+        ctx.fillText(DATA.letters[k], j * s + s / 2, i * s + s - 2);
+      }
+    });
+  });
+  const scores = document.getElementById('scores');
+  scores.innerHTML = '';
+  DATA.names.forEach((name, k) => {
+    const row = document.createElement('div');
+    row.style.color = DATA.colors[k];
+    row.textContent = name + ': ' + frame.scores[k];
+    scores.appendChild(row);
+  });
+  timeline.value = t;
+  document.getElementById('tick').textContent = 'tick ' + t + ' / ' + (DATA.frames.length - 1);
+  const marker = document.getElementById('marker');
+  const x = t / Math.max(1, DATA.frames.length - 1) * 600;
+  marker.setAttribute('x1', x);
+  marker.setAttribute('x2', x);
+}
+
+function pause() {
+  clearInterval(timer);
+  timer = null;
+  button.textContent = 'play';
+}
+
+button.onclick = () => {
+  if (timer !== null) {
+    pause();
+    return;
+  }
+  if (current + 1 >= DATA.frames.length) {
+    draw(0);
+  }
+  button.textContent = 'pause';
+  timer = setInterval(() => {
+    if (current + 1 < DATA.frames.length) {
+      draw(current + 1);
+    } else {
+      pause();
+    }
+  }, 40);
+};
+timeline.oninput = () => draw(parseInt(timeline.value, 10));
+draw(0);
+</script>
+</body>
+</html>
+"##;
@@ -0,0 +1,480 @@
+//! Saving and loading of `Replay`.
+//!
+//! The text variant is meant to be read by humans:
+//! ```text
+//! xcg-replay 3
+//! height=5
+//! width=7
+//! duration=20
+//! ratio=0.9
+//! random_seed=69
+//! player=A
+//! player=B
+//! moves=
+//! dl
+//! ls
+//! ```
+//! `random_seed` is absent if the match wasn't seeded, `rules=simultaneous` is present
+//! only for the simultaneous rules, `map=` lines are present only for the custom field,
+//! one per row with `*` for the border, `.` for the empty cell and `#` for the hole.
+//! Each line after `moves=` is a tick with the move letters in the player order (like in `TestBot`).
+//!
+//! The binary variant is compact, all numbers are little endian:
+//! magic `XCGR`, version `u16`, height `u16`, width `u16`, duration `u32`, ratio `f32`,
+//! seed flag `u8` followed by the seed `u64` if the flag is 1, rules `u8` (0 sequential,
+//! 1 simultaneous, absent in version 1), map flag `u8` followed by `height * width` cells
+//! if the flag is 1 (0 empty, 1 border, 2 hole, absent before version 3), players count `u8`,
+//! each name as `u16` length and UTF-8 bytes, ticks count `u32`, then the moves
+//! packed 2 per byte (the first move in the low nibble).
+use crate::board::Cells;
+use crate::model::Cell;
+use crate::model::Field;
+use crate::model::MAX_PLAYERS;
+use crate::model::Move;
+use crate::model::Replay;
+use crate::model::Rules;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufWriter, Read, Write};
+use std::path::Path;
+
+/// version 2 added the rules, version 3 added the map, the older versions are still readable
+pub const VERSION: u16 = 3;
+
+/// the smallest field that can be played, the same as `xcg-app` accepts
+const MIN_SIZE: usize = 3;
+
+const TEXT_MAGIC: &str = "xcg-replay";
+const BINARY_MAGIC: &[u8; 4] = b"XCGR";
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    Malformed { line: usize, reason: String },
+    /// the binary variant has no lines, `offset` is the position of the bad byte
+    MalformedBinary { offset: usize, reason: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "I/O error: {}", e),
+            ReplayError::BadMagic => write!(f, "Not a replay file"),
+            ReplayError::UnsupportedVersion(v) => write!(f, "Unsupported replay version {}", v),
+            ReplayError::Malformed { line, reason } => write!(f, "Malformed replay at line {}: {}", line, reason),
+            ReplayError::MalformedBinary { offset, reason } => write!(f, "Malformed replay at byte {}: {}", offset, reason),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn description(&self) -> &str { "Cannot read the replay" }
+    fn cause(&self) -> Option<&dyn Error> {
+        match self {
+            ReplayError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+/// save the replay to the file, `binary` selects the variant
+pub fn save_replay<P: AsRef<Path>>(path: P, replay: &Replay, binary: bool) -> Result<(), ReplayError> {
+    let mut w = BufWriter::new(File::create(path)?);
+    if binary {
+        write_binary(&mut w, replay)?;
+    } else {
+        write_text(&mut w, replay)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// load the replay from the file, the variant is detected by the magic
+pub fn load_replay<P: AsRef<Path>>(path: P) -> Result<Replay, ReplayError> {
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    if bytes.starts_with(BINARY_MAGIC) {
+        read_binary(&mut &bytes[..])
+    } else {
+        read_text(&mut &bytes[..])
+    }
+}
+
+pub fn write_text(w: &mut dyn Write, replay: &Replay) -> io::Result<()> {
+    writeln!(w, "{} {}", TEXT_MAGIC, VERSION)?;
+    writeln!(w, "height={}", replay.height)?;
+    writeln!(w, "width={}", replay.width)?;
+    writeln!(w, "duration={}", replay.duration)?;
+    writeln!(w, "ratio={}", replay.ratio)?;
+    if let Some(seed) = replay.random_seed {
+        writeln!(w, "random_seed={}", seed)?;
+    }
+    if replay.rules == Rules::Simultaneous {
+        writeln!(w, "rules=simultaneous")?;
+    }
+    if let Some(ref map) = replay.map {
+        for row in &map.cells {
+            let line: String = row.iter().map(|&c| cell_to_char(c)).collect();
+            writeln!(w, "map={}", line)?;
+        }
+    }
+    for name in &replay.player_names {
+        writeln!(w, "player={}", name)?;
+    }
+    writeln!(w, "moves=")?;
+    for tick in &replay.moves {
+        let line: String = tick.iter().map(|&m| move_to_char(m)).collect();
+        writeln!(w, "{}", line)?;
+    }
+    Ok(())
+}
+
+pub fn read_text(r: &mut dyn BufRead) -> Result<Replay, ReplayError> {
+    let mut lines = vec![];
+    for line in r.lines() {
+        lines.push(line?);
+    }
+    let malformed = |line: usize, reason: &str| ReplayError::Malformed { line, reason: reason.to_string() };
+    // check the header first
+    let header = lines.first().ok_or(ReplayError::BadMagic)?;
+    let mut header = header.split_whitespace();
+    if header.next() != Some(TEXT_MAGIC) {
+        return Err(ReplayError::BadMagic);
+    }
+    let version = header.next()
+        .and_then(|v| v.parse::<u16>().ok())
+        .ok_or_else(|| malformed(1, "no version"))?;
+    if version == 0 || version > VERSION {
+        return Err(ReplayError::UnsupportedVersion(version));
+    }
+    let mut height = None;
+    let mut width = None;
+    let mut duration = None;
+    let mut ratio = None;
+    let mut random_seed = None;
+    let mut rules = Rules::Sequential;
+    let mut map_rows: Vec<Vec<Cell>> = vec![];
+    let mut player_names = vec![];
+    let mut moves = vec![];
+    let mut in_moves = false;
+    for (k, line) in lines.iter().enumerate().skip(1) {
+        let no = k + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if in_moves {
+            let tick = line.chars()
+                .map(char_to_move)
+                .collect::<Option<Vec<Move>>>()
+                .ok_or_else(|| malformed(no, "invalid move"))?;
+            if tick.len() != player_names.len() {
+                return Err(malformed(no, "moves don't match players"));
+            }
+            moves.push(tick);
+            continue;
+        }
+        let mut kv = line.splitn(2, '=');
+        let key = kv.next().unwrap_or("").trim();
+        let value = kv.next().ok_or_else(|| malformed(no, "expected key=value"))?.trim();
+        let bad_number = || malformed(no, "invalid number");
+        let size = || match value.parse::<usize>() {
+            Ok(size) if size < MIN_SIZE => Err(malformed(no, "the field is too small")),
+            Ok(size) => Ok(size),
+            Err(_) => Err(bad_number()),
+        };
+        match key {
+            "height" => height = Some(size()?),
+            "width" => width = Some(size()?),
+            "duration" => duration = Some(value.parse::<u32>().map_err(|_| bad_number())?),
+            "ratio" => ratio = Some(value.parse::<f32>().map_err(|_| bad_number())?),
+            "random_seed" => random_seed = Some(value.parse::<u64>().map_err(|_| bad_number())?),
+            "rules" if version >= 2 => rules = match value {
+                "sequential" => Rules::Sequential,
+                "simultaneous" => Rules::Simultaneous,
+                _ => return Err(malformed(no, "unknown rules")),
+            },
+            "map" if version >= 3 => map_rows.push(value.chars()
+                .map(char_to_cell)
+                .collect::<Option<Vec<Cell>>>()
+                .ok_or_else(|| malformed(no, "invalid map cell"))?),
+            "player" if player_names.len() == MAX_PLAYERS => return Err(malformed(no, "too many players")),
+            "player" => player_names.push(value.to_string()),
+            "moves" if player_names.is_empty() => return Err(malformed(no, "no players")),
+            "moves" => in_moves = true,
+            _ => return Err(malformed(no, "unknown key")),
+        }
+    }
+    let last = lines.len();
+    let height = height.ok_or_else(|| malformed(last, "no height"))?;
+    let width = width.ok_or_else(|| malformed(last, "no width"))?;
+    if player_names.is_empty() {
+        return Err(malformed(last, "no players"));
+    }
+    let map = if map_rows.is_empty() {
+        None
+    } else if map_rows.len() != height || map_rows.iter().any(|row| row.len() != width) {
+        return Err(malformed(last, "map doesn't match the size"));
+    } else {
+        Some(Field { m: height, n: width, cells: map_rows.into() })
+    };
+    Ok(Replay {
+        height,
+        width,
+        duration: duration.ok_or_else(|| malformed(last, "no duration"))?,
+        ratio: ratio.ok_or_else(|| malformed(last, "no ratio"))?,
+        player_names,
+        moves,
+        random_seed,
+        rules,
+        map,
+    })
+}
+
+/// fails with `InvalidInput` if the sizes, the players or the names don't fit the format
+pub fn write_binary(w: &mut dyn Write, replay: &Replay) -> io::Result<()> {
+    let too_large = |what: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is too large for the binary replay", what));
+    let height = u16::try_from(replay.height).map_err(|_| too_large("height"))?;
+    let width = u16::try_from(replay.width).map_err(|_| too_large("width"))?;
+    let np = u8::try_from(replay.player_names.len()).map_err(|_| too_large("players count"))?;
+    let ticks = u32::try_from(replay.moves.len()).map_err(|_| too_large("ticks count"))?;
+    if replay.player_names.iter().any(|name| name.len() > u16::MAX as usize) {
+        return Err(too_large("player name"));
+    }
+    if replay.moves.iter().any(|tick| tick.len() != replay.player_names.len()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "moves don't match players"));
+    }
+    w.write_all(BINARY_MAGIC)?;
+    w.write_u16::<LittleEndian>(VERSION)?;
+    w.write_u16::<LittleEndian>(height)?;
+    w.write_u16::<LittleEndian>(width)?;
+    w.write_u32::<LittleEndian>(replay.duration)?;
+    w.write_f32::<LittleEndian>(replay.ratio)?;
+    match replay.random_seed {
+        Some(seed) => {
+            w.write_u8(1)?;
+            w.write_u64::<LittleEndian>(seed)?;
+        }
+        None => w.write_u8(0)?,
+    }
+    w.write_u8(match replay.rules {
+        Rules::Sequential => 0,
+        Rules::Simultaneous => 1,
+    })?;
+    match replay.map {
+        Some(ref map) => {
+            w.write_u8(1)?;
+            for row in &map.cells {
+                for &cell in row {
+                    w.write_u8(cell_to_code(cell))?;
+                }
+            }
+        }
+        None => w.write_u8(0)?,
+    }
+    w.write_u8(np)?;
+    for name in &replay.player_names {
+        w.write_u16::<LittleEndian>(name.len() as u16)?;
+        w.write_all(name.as_bytes())?;
+    }
+    w.write_u32::<LittleEndian>(ticks)?;
+    for tick in &replay.moves {
+        for pair in tick.chunks(2) {
+            let lo = move_to_code(pair[0]);
+            let hi = if pair.len() > 1 { move_to_code(pair[1]) } else { 0 };
+            w.write_u8(lo | (hi << 4))?;
+        }
+    }
+    Ok(())
+}
+
+/// counts the bytes read to report the position of the malformed data
+struct Counted<'a> {
+    inner: &'a mut dyn Read,
+    offset: usize,
+}
+
+impl<'a> Read for Counted<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let k = self.inner.read(buf)?;
+        self.offset += k;
+        Ok(k)
+    }
+}
+
+/// the sizes and the counts are not trusted, the vectors grow as the data is read
+pub fn read_binary(r: &mut dyn Read) -> Result<Replay, ReplayError> {
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != BINARY_MAGIC {
+        return Err(ReplayError::BadMagic);
+    }
+    let mut r = Counted { inner: r, offset: magic.len() };
+    let version = r.read_u16::<LittleEndian>()?;
+    if version == 0 || version > VERSION {
+        return Err(ReplayError::UnsupportedVersion(version));
+    }
+    // `offset` is the position of the bad byte in the input
+    let malformed = |offset: usize, reason: &str| ReplayError::MalformedBinary { offset, reason: reason.to_string() };
+    let height = r.read_u16::<LittleEndian>()? as usize;
+    if height < MIN_SIZE {
+        return Err(malformed(r.offset - 2, "the field is too small"));
+    }
+    let width = r.read_u16::<LittleEndian>()? as usize;
+    if width < MIN_SIZE {
+        return Err(malformed(r.offset - 2, "the field is too small"));
+    }
+    let duration = r.read_u32::<LittleEndian>()?;
+    let ratio = r.read_f32::<LittleEndian>()?;
+    let random_seed = match r.read_u8()? {
+        0 => None,
+        1 => Some(r.read_u64::<LittleEndian>()?),
+        _ => return Err(malformed(r.offset - 1, "invalid seed flag")),
+    };
+    let rules = if version < 2 {
+        Rules::Sequential
+    } else {
+        match r.read_u8()? {
+            0 => Rules::Sequential,
+            1 => Rules::Simultaneous,
+            _ => return Err(malformed(r.offset - 1, "invalid rules")),
+        }
+    };
+    let map = if version < 3 {
+        None
+    } else {
+        match r.read_u8()? {
+            0 => None,
+            1 => {
+                let mut rows = vec![];
+                let mut buf = vec![0u8; width];
+                for _ in 0..height {
+                    r.read_exact(&mut buf)?;
+                    let start = r.offset - width;
+                    let row = buf.iter().enumerate()
+                        .map(|(j, &code)| code_to_cell(code).ok_or_else(|| malformed(start + j, "invalid map cell")))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    rows.push(row);
+                }
+                Some(Field { m: height, n: width, cells: Cells::from(rows) })
+            }
+            _ => return Err(malformed(r.offset - 1, "invalid map flag")),
+        }
+    };
+    let np = r.read_u8()? as usize;
+    if np == 0 || np > MAX_PLAYERS {
+        let reason = if np == 0 { "no players" } else { "too many players" };
+        return Err(malformed(r.offset - 1, reason));
+    }
+    let mut player_names = Vec::with_capacity(np);
+    for _ in 0..np {
+        let len = r.read_u16::<LittleEndian>()? as usize;
+        let mut buf = vec![0u8; len];
+        r.read_exact(&mut buf)?;
+        let name = String::from_utf8(buf).map_err(|_| malformed(r.offset - len, "invalid player name"))?;
+        player_names.push(name);
+    }
+    let ticks = r.read_u32::<LittleEndian>()? as usize;
+    let mut buf = vec![0u8; np.div_ceil(2)];
+    let mut moves = vec![];
+    for _ in 0..ticks {
+        r.read_exact(&mut buf)?;
+        let start = r.offset - buf.len();
+        let mut tick = Vec::with_capacity(np);
+        for k in 0..np {
+            let code = if k % 2 == 0 { buf[k / 2] & 0x0F } else { buf[k / 2] >> 4 };
+            tick.push(code_to_move(code).ok_or_else(|| malformed(start + k / 2, "invalid move"))?);
+        }
+        moves.push(tick);
+    }
+    Ok(Replay { height, width, duration, ratio, player_names, moves, random_seed, rules, map })
+}
+
+fn move_to_char(m: Move) -> char {
+    match m {
+        Move::Right => 'r',
+        Move::Up => 'u',
+        Move::Left => 'l',
+        Move::Down => 'd',
+        Move::Stop => 's',
+    }
+}
+
+fn char_to_move(c: char) -> Option<Move> {
+    match c {
+        'r' => Some(Move::Right),
+        'u' => Some(Move::Up),
+        'l' => Some(Move::Left),
+        'd' => Some(Move::Down),
+        's' => Some(Move::Stop),
+        _ => None,
+    }
+}
+
+fn move_to_code(m: Move) -> u8 {
+    match m {
+        Move::Right => 0,
+        Move::Up => 1,
+        Move::Left => 2,
+        Move::Down => 3,
+        Move::Stop => 4,
+    }
+}
+
+fn code_to_move(code: u8) -> Option<Move> {
+    match code {
+        0 => Some(Move::Right),
+        1 => Some(Move::Up),
+        2 => Some(Move::Left),
+        3 => Some(Move::Down),
+        4 => Some(Move::Stop),
+        _ => None,
+    }
+}
+
+/// the maps have only the border, the empty cells and the holes
+fn cell_to_char(cell: Cell) -> char {
+    match cell {
+        Cell::Border => '*',
+        Cell::Void => '#',
+        _ => '.',
+    }
+}
+
+fn char_to_cell(c: char) -> Option<Cell> {
+    match c {
+        '*' => Some(Cell::Border),
+        '#' => Some(Cell::Void),
+        '.' => Some(Cell::Empty),
+        _ => None,
+    }
+}
+
+fn cell_to_code(cell: Cell) -> u8 {
+    match cell {
+        Cell::Border => 1,
+        Cell::Void => 2,
+        _ => 0,
+    }
+}
+
+fn code_to_cell(code: u8) -> Option<Cell> {
+    match code {
+        0 => Some(Cell::Empty),
+        1 => Some(Cell::Border),
+        2 => Some(Cell::Void),
+        _ => None,
+    }
+}
@@ -0,0 +1,319 @@
+use crate::model::*;
+use itertools::Itertools;
+use rand::IsaacRng;
+use rand::prelude::RngCore;
+use rand::SeedableRng;
+use std::collections::HashSet;
+use std::io;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// creates the bot for the given player index, the factory is shared between the workers,
+/// but the bot is created and used by the single worker, so bots don't have to be `Send`
+pub type BotFactory = Box<dyn Fn(usize) -> Box<dyn Bot> + Send + Sync>;
+
+pub struct Entry {
+    pub name: String,
+    pub factory: BotFactory,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Pairing {
+    /// every group of `players` bots from the roster meets
+    RoundRobin,
+    /// bots with the close ratings meet, groups are rebuilt after each round,
+    /// the groups that have already met are avoided while there are others
+    Swiss { rounds: usize },
+}
+
+/// Settings of the tournament
+/// - `players` the number of bots in a match
+/// - `matches` the number of seeds (i.e. different fields) for each group of bots
+/// - `rotate` if true each seed is played with all rotations of the seats,
+///   so every bot gets every origin and every place in `reordering`
+/// - `map` the custom field, `height` and `width` are ignored if it is set
+/// - `rules` how the moves are applied, see `Rules`
+/// - `workers` the number of threads to run the matches, 0 is the same as 1
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub height: usize,
+    pub width: usize,
+    pub duration: u32,
+    pub ratio: f32,
+    pub players: usize,
+    pub matches: usize,
+    pub rotate: bool,
+    pub pairing: Pairing,
+    pub seed: u64,
+    pub map: Option<Field>,
+    pub rules: Rules,
+    pub workers: usize,
+}
+
+/// The single match to be played, `seats[k]` is the roster index of the player `k`
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Job {
+    pub seed: u64,
+    pub seats: Vec<usize>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MatchResult {
+    pub job: Job,
+    pub stats: Stats,
+    pub timings: Vec<Timing>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Standing {
+    pub name: String,
+    pub matches: u32,
+    pub wins: f64,
+    pub score: u64,
+    pub rating: f64,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TournamentResult {
+    pub matches: Vec<MatchResult>,
+    pub standings: Vec<Standing>,
+}
+
+pub const INITIAL_RATING: f64 = 1500.0;
+const ELO_K: f64 = 32.0;
+
+impl Standing {
+    pub fn win_rate(&self) -> f64 {
+        if self.matches == 0 { 0.0 } else { self.wins / f64::from(self.matches) }
+    }
+}
+
+/// all the groups of `k` from `n` roster entries, in lexicographic order
+pub fn round_robin(n: usize, k: usize) -> Vec<Vec<usize>> {
+    fn go(start: usize, n: usize, k: usize, cur: &mut Vec<usize>, result: &mut Vec<Vec<usize>>) {
+        if cur.len() == k {
+            result.push(cur.clone());
+            return;
+        }
+        for x in start..n {
+            cur.push(x);
+            go(x + 1, n, k, cur, result);
+            cur.pop();
+        }
+    }
+    let mut result = vec![];
+    if k > 0 && k <= n {
+        go(0, n, k, &mut Vec::with_capacity(k), &mut result);
+    }
+    result
+}
+
+/// split the roster into groups of `k` by the rating, the best go first,
+/// the ones that don't fill the whole group sit out the round;
+/// the best one left joins the closest rated that make a group not in `played`
+/// (the groups are sorted there), the closest rated ones if all such groups have played
+pub fn swiss_round(ratings: &[f64], k: usize, played: &HashSet<Vec<usize>>) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..ratings.len()).collect();
+    order.sort_by(|&a, &b| ratings[b].partial_cmp(&ratings[a]).unwrap_or(std::cmp::Ordering::Equal).then(a.cmp(&b)));
+    if k == 0 {
+        return vec![];
+    }
+    let mut groups = vec![];
+    while order.len() >= k {
+        let picks = first_new_group(&order, k, played).unwrap_or_else(|| (0..k).collect());
+        groups.push(picks.iter().map(|&p| order[p]).collect());
+        for &p in picks.iter().rev() {
+            order.remove(p);
+        }
+    }
+    groups
+}
+
+/// the positions in `order` of the first group with `order[0]` that is not in `played`,
+/// each rejected group is in `played`, so the search is as long as the history at most
+fn first_new_group(order: &[usize], k: usize, played: &HashSet<Vec<usize>>) -> Option<Vec<usize>> {
+    fn go(order: &[usize], k: usize, played: &HashSet<Vec<usize>>, start: usize, cur: &mut Vec<usize>) -> bool {
+        if cur.len() == k {
+            let mut group: Vec<usize> = cur.iter().map(|&p| order[p]).collect();
+            group.sort_unstable();
+            return !played.contains(&group);
+        }
+        for p in start..order.len() {
+            cur.push(p);
+            if go(order, k, played, p + 1, cur) {
+                return true;
+            }
+            cur.pop();
+        }
+        false
+    }
+    let mut cur = vec![0];
+    if go(order, k, played, 1, &mut cur) { Some(cur) } else { None }
+}
+
+/// all cyclic shifts of the group, so each bot gets each seat once
+pub fn rotations(group: &[usize]) -> Vec<Vec<usize>> {
+    (0..group.len()).map(|s| {
+        group.iter().cycle().skip(s).take(group.len()).cloned().collect()
+    }).collect()
+}
+
+/// create the jobs for the groups, seeds are taken from `random`
+pub fn make_jobs(settings: &Settings, groups: &[Vec<usize>], random: &mut dyn RngCore) -> Vec<Job> {
+    let mut jobs = vec![];
+    for group in groups {
+        for _ in 0..settings.matches {
+            let seed = random.next_u64();
+            let seatings = if settings.rotate { rotations(group) } else { vec![group.clone()] };
+            for seats in seatings {
+                jobs.push(Job { seed, seats });
+            }
+        }
+    }
+    jobs
+}
+
+pub fn run_job(settings: &Settings, roster: &[Entry], job: &Job) -> MatchResult {
+    let names: Vec<&str> = job.seats.iter().map(|&r| roster[r].name.as_str()).collect();
+    let mut bots: Vec<Box<dyn Bot>> = job.seats.iter().enumerate()
+        .map(|(k, &r)| (roster[r].factory)(k))
+        .collect();
+    let field = settings.map.clone().unwrap_or_else(|| create_default_field(settings.height, settings.width));
+    let mut the_match = create_match_on(field, &names, settings.duration, settings.ratio, Some(job.seed));
+    the_match.rules = settings.rules;
+    run_match(&mut the_match, &mut bots, &|_| {});
+    MatchResult { job: job.clone(), stats: the_match.game_state.stats, timings: the_match.timings }
+}
+
+/// run the jobs on `settings.workers` threads, the results are in the order of the jobs
+pub fn run_batch(settings: &Settings, roster: &[Entry], jobs: &[Job]) -> Vec<MatchResult> {
+    let workers = settings.workers.max(1).min(jobs.len());
+    if workers <= 1 {
+        return jobs.iter().map(|job| run_job(settings, roster, job)).collect();
+    }
+    // the workers take the jobs one by one, so the slow matches don't stall the others
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, MatchResult)> = crossbeam::scope(|scope| {
+        let handles: Vec<_> = (0..workers).map(|_| {
+            scope.spawn(|| {
+                let mut done = vec![];
+                loop {
+                    let k = next.fetch_add(1, Ordering::SeqCst);
+                    if k >= jobs.len() {
+                        break done;
+                    }
+                    done.push((k, run_job(settings, roster, &jobs[k])));
+                }
+            })
+        }).collect();
+        handles.into_iter().flat_map(|h| h.join()).collect()
+    });
+    results.sort_by_key(|(k, _)| *k);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+pub fn run_tournament(settings: &Settings, roster: &[Entry], logger: &dyn Fn(&MatchResult)) -> TournamentResult {
+    let mut random = IsaacRng::seed_from_u64(settings.seed);
+    let mut standings: Vec<Standing> = roster.iter().map(|e| Standing {
+        name: e.name.clone(),
+        matches: 0,
+        wins: 0.0,
+        score: 0,
+        rating: INITIAL_RATING,
+    }).collect();
+    let rounds = match settings.pairing {
+        Pairing::RoundRobin => 1,
+        Pairing::Swiss { rounds } => rounds,
+    };
+    let mut matches = vec![];
+    let mut played: HashSet<Vec<usize>> = HashSet::new();
+    for _ in 0..rounds {
+        let groups = match settings.pairing {
+            Pairing::RoundRobin => round_robin(roster.len(), settings.players),
+            Pairing::Swiss { .. } => {
+                let ratings: Vec<f64> = standings.iter().map(|s| s.rating).collect();
+                swiss_round(&ratings, settings.players, &played)
+            }
+        };
+        for group in &groups {
+            let mut group = group.clone();
+            group.sort_unstable();
+            played.insert(group);
+        }
+        let jobs = make_jobs(settings, &groups, &mut random);
+        for result in run_batch(settings, roster, &jobs) {
+            update_standings(&mut standings, &result);
+            logger(&result);
+            matches.push(result);
+        }
+    }
+    TournamentResult { matches, standings }
+}
+
+/// count the match in, the winners share the win,
+/// the rating is Elo, where the match is the set of duels between each pair of players
+pub fn update_standings(standings: &mut [Standing], result: &MatchResult) {
+    let seats = &result.job.seats;
+    let scores = &result.stats.scores;
+    let np = seats.len();
+    let best = scores.iter().cloned().max().unwrap_or(0);
+    let winners = scores.iter().filter(|&&s| s == best).count() as f64;
+    let ratings: Vec<f64> = seats.iter().map(|&r| standings[r].rating).collect();
+    for k in 0..np {
+        let standing = &mut standings[seats[k]];
+        standing.matches += 1;
+        standing.score += u64::from(scores[k]);
+        if scores[k] == best {
+            standing.wins += 1.0 / winners;
+        }
+        if np > 1 {
+            let mut delta = 0.0;
+            for l in (0..np).filter(|&l| l != k) {
+                let actual = if scores[k] > scores[l] { 1.0 } else if scores[k] == scores[l] { 0.5 } else { 0.0 };
+                let expected = 1.0 / (1.0 + 10f64.powf((ratings[l] - ratings[k]) / 400.0));
+                delta += actual - expected;
+            }
+            standing.rating += ELO_K * delta / (np - 1) as f64;
+        }
+    }
+}
+
+/// the same tab separated columns as the output of `xcg-app tournament`, `score_a` is the score
+/// of the player 0 and so on, see `write_matches_header` for `seats`
+pub fn write_matches_tsv(w: &mut dyn Write, matches: &[MatchResult], seats: bool) -> io::Result<()> {
+    let np = matches.iter().map(|r| r.stats.scores.len()).max().unwrap_or(0);
+    write_matches_header(w, np, seats)?;
+    for (it, result) in matches.iter().enumerate() {
+        write_match_row(w, it, result, seats)?;
+    }
+    Ok(())
+}
+
+/// the score columns are `score_a` to `score_z`, then `score_26` and so on,
+/// if `seats` the last column is the roster indices of the players, separated by commas
+pub fn write_matches_header(w: &mut dyn Write, np: usize, seats: bool) -> io::Result<()> {
+    let scores: Vec<String> = (0..np).map(|k| {
+        if k < 26 { format!("score_{}", (b'a' + k as u8) as char) } else { format!("score_{}", k) }
+    }).collect();
+    let seats = if seats { "\tseats" } else { "" };
+    writeln!(w, "it\titeration\touroboros_count\tbite_count\thead_to_head_count\tseed\t{}{}", scores.join("\t"), seats)
+}
+
+pub fn write_match_row(w: &mut dyn Write, it: usize, result: &MatchResult, seats: bool) -> io::Result<()> {
+    let stats = &result.stats;
+    let scores: Vec<String> = stats.scores.iter().map(|s| s.to_string()).collect();
+    write!(w, "{:06}\t{}\t{}\t{}\t{}\t{}\t{}", it, stats.iteration, stats.ouroboros_count,
+           stats.bite_count, stats.head_to_head_count, result.job.seed, scores.join("\t"))?;
+    if seats {
+        write!(w, "\t{}", result.job.seats.iter().join(","))?;
+    }
+    writeln!(w)
+}
+
+pub fn write_standings_tsv(w: &mut dyn Write, standings: &[Standing]) -> io::Result<()> {
+    writeln!(w, "name\tmatches\twins\twin_rate\tscore\trating")?;
+    for s in standings {
+        writeln!(w, "{}\t{}\t{:.2}\t{:.4}\t{}\t{:.1}", s.name, s.matches, s.wins, s.win_rate(), s.score, s.rating)?;
+    }
+    Ok(())
+}
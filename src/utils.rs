@@ -2,6 +2,9 @@ use std::fmt;
 use itertools::Itertools;
 use core::cmp;
 use core::num::Wrapping as W;
+use console::{Key, Term};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 
 // use
 //fn undef() -> ! {
@@ -144,3 +147,58 @@ impl fmt::Debug for IsaacCore0 {
 //    }
 //    result
 //}
+
+/// Read the keys from the terminal in the background thread, the thread stops
+/// when the receiver is dropped and the next key comes. If the input is not a terminal,
+/// nothing is read and the receiver is disconnected.
+pub fn spawn_key_reader() -> Receiver<Key> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let term = Term::stdout();
+        if !term.is_term() {
+            return;
+        }
+        while let Ok(key) = term.read_key() {
+            if tx.send(key).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Restores the terminal settings when dropped. The key reader thread leaves the terminal
+/// in the raw mode if the program ends while the thread waits for a key.
+pub struct TermGuard {
+    #[cfg(unix)]
+    original: Option<termios::Termios>,
+}
+
+impl TermGuard {
+    #[cfg(unix)]
+    pub fn new() -> TermGuard {
+        TermGuard { original: termios::Termios::from_fd(0).ok() }
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> TermGuard {
+        TermGuard {}
+    }
+}
+
+impl Default for TermGuard {
+    fn default() -> Self {
+        TermGuard::new()
+    }
+}
+
+impl Drop for TermGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            if let Some(ref original) = self.original {
+                let _ = termios::tcsetattr(0, termios::TCSADRAIN, original);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Interactive replay viewer.
+//!
+//! `ViewerState` keeps the snapshot after every player's move (after every tick if the moves
+//! are simultaneous), so stepping back and seeking are just moving the position.
+//! The state doesn't touch the terminal, the loop in `xcg-app view` feeds it the keys
+//! and prints `render()` until `on_key` returns false.
+
+use crate::model::*;
+use console::Key;
+use std::cell::RefCell;
+use std::time::Duration;
+
+const MIN_DELAY: Duration = Duration::from_millis(5);
+const MAX_DELAY: Duration = Duration::from_millis(2000);
+
+pub const HELP: &str = "space: play/pause, ←/→: tick, h/l: move, +/-: speed, g/G: first/last, \
+                        <n> enter: go to the tick n, q: quit";
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Command {
+    /// one player's move forward or back
+    NextMove,
+    PrevMove,
+    /// to the end of the next tick, to the end of the previous one
+    NextTick,
+    PrevTick,
+    /// to the end of the tick, 0 is the start
+    Jump(u32),
+    First,
+    Last,
+    TogglePause,
+    Faster,
+    Slower,
+    Quit,
+}
+
+#[derive(Clone, Debug)]
+pub struct ViewerState {
+    /// the start and the state after each step
+    frames: Vec<GameState>,
+    /// the number of steps in the tick
+    per_tick: usize,
+    pub pos: usize,
+    pub playing: bool,
+    pub delay: Duration,
+    /// the digits typed for the jump
+    pub input: String,
+}
+
+impl ViewerState {
+    pub fn new(replay: &Replay, delay: Duration) -> ViewerState {
+        let per_tick = match replay.rules {
+            Rules::Sequential => replay.player_names.len().max(1),
+            Rules::Simultaneous => 1,
+        };
+        let frames = RefCell::new(vec![replay_start(replay)]);
+        run_replay(replay, &|gs| frames.borrow_mut().push(gs.clone()));
+        let frames = frames.into_inner();
+        ViewerState { frames, per_tick, pos: 0, playing: false, delay, input: String::new() }
+    }
+
+    pub fn current(&self) -> &GameState {
+        &self.frames[self.pos]
+    }
+
+    /// the number of the steps, the positions are from 0 to `len()` inclusive
+    pub fn len(&self) -> usize {
+        self.frames.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the number of the whole ticks made
+    pub fn tick(&self) -> usize {
+        self.pos / self.per_tick
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.len() / self.per_tick
+    }
+
+    /// returns false if the viewer should quit
+    pub fn apply(&mut self, command: Command) -> bool {
+        let pt = self.per_tick;
+        match command {
+            Command::NextMove => self.pos = (self.pos + 1).min(self.len()),
+            Command::PrevMove => self.pos = self.pos.saturating_sub(1),
+            Command::NextTick => self.pos = ((self.pos / pt + 1) * pt).min(self.len()),
+            Command::PrevTick => self.pos = (self.pos.saturating_sub(1) / pt) * pt,
+            Command::Jump(tick) => self.pos = (tick as usize * pt).min(self.len()),
+            Command::First => self.pos = 0,
+            Command::Last => self.pos = self.len(),
+            Command::TogglePause => {
+                if !self.playing && self.pos == self.len() {
+                    self.pos = 0;
+                }
+                self.playing = !self.playing;
+            }
+            Command::Faster => self.delay = (self.delay / 2).max(MIN_DELAY),
+            Command::Slower => self.delay = (self.delay * 2).min(MAX_DELAY),
+            Command::Quit => return false,
+        }
+        true
+    }
+
+    /// the key pressed, returns false if the viewer should quit
+    pub fn on_key(&mut self, key: Key) -> bool {
+        let command = match key {
+            Key::Char(c) if c.is_ascii_digit() => {
+                self.input.push(c);
+                return true;
+            }
+            Key::Enter if !self.input.is_empty() => {
+                let tick = self.input.parse().unwrap_or(u32::MAX);
+                self.input.clear();
+                Command::Jump(tick)
+            }
+            Key::ArrowRight => Command::NextTick,
+            Key::ArrowLeft => Command::PrevTick,
+            Key::Char('l') => Command::NextMove,
+            Key::Char('h') => Command::PrevMove,
+            Key::Char(' ') => Command::TogglePause,
+            Key::Char('+') | Key::Char('=') => Command::Faster,
+            Key::Char('-') => Command::Slower,
+            Key::Char('g') => Command::First,
+            Key::Char('G') => Command::Last,
+            Key::Char('q') | Key::Escape => Command::Quit,
+            _ => return true,
+        };
+        self.input.clear();
+        self.apply(command)
+    }
+
+    /// the time has come for the next frame, the playing stops at the end
+    pub fn advance(&mut self) {
+        if self.playing {
+            self.apply(Command::NextTick);
+            if self.pos == self.len() {
+                self.playing = false;
+            }
+        }
+    }
+
+    /// the cells captured by the last step
+    pub fn flooded(&self) -> Vec<Point> {
+        if self.pos == 0 {
+            return vec![];
+        }
+        let before = self.frames[self.pos - 1].field.cells.as_slice();
+        let field = &self.current().field;
+        field.cells.as_slice().iter().zip(before).enumerate()
+            .filter(|(_, (&now, &was))| now != was && matches!(now, Cell::Owned(_)))
+            .map(|(k, _)| Point((k / field.n) as i16, (k % field.n) as i16))
+            .collect()
+    }
+
+    /// the field with the captured cells highlighted and the status line
+    pub fn render(&self, use_colors: bool) -> String {
+        let mut result = prettify_impl(self.current(), false, use_colors, false, &self.flooded());
+        let step = match self.pos % self.per_tick {
+            0 => String::new(),
+            k => format!(", move {}/{}", k, self.per_tick),
+        };
+        result.push_str(&format!(
+            "tick {}/{}{}, {}, {}ms{}\n{}\n",
+            self.tick(), self.ticks(), step,
+            if self.playing { "playing" } else { "paused" },
+            self.delay.as_millis(),
+            if self.input.is_empty() { String::new() } else { format!(", go to {}", self.input) },
+            HELP,
+        ));
+        result
+    }
+}
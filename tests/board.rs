@@ -0,0 +1,46 @@
+use xcg::board::{BitBoard, Cells};
+use xcg::model::*;
+
+#[test]
+fn test_cells_and_boards() {
+    let gs = GameState::parse_string(r#"
+        *.*.*.*.*.
+        *.0 0 1 *.
+        *.1   0 *A
+        *.*.*.*B*.
+    "#).unwrap();
+    let cells: &Cells = &gs.field.cells;
+    assert_eq!((4, 5), (cells.rows(), cells.cols()));
+    assert_eq!(Cell::Owned(1), cells[1][3]);
+    assert_eq!(Cell::Owned(1), cells.get(Point(2, 1)));
+    assert_eq!(&cells.as_slice()[10..15], &cells[2]);
+    assert_eq!(4, cells.iter().count());
+
+    assert_eq!(vec![Point(1, 1), Point(1, 2), Point(2, 3)], cells.owned(0).points());
+    assert_eq!(vec![Point(1, 3), Point(2, 1)], cells.owned(1).points());
+    assert!(cells.owned(2).is_empty());
+    assert_eq!(cells.board(|c| c != Cell::Empty), cells.filled());
+    let empty = cells.board(|c| c == Cell::Empty);
+    assert_eq!(1, empty.len());
+    assert!(empty.contains(Point(2, 2)));
+    assert!(!empty.contains(Point(-1, 2)) && !empty.contains(Point(2, 5)));
+
+    // the boards follow the changes, and the cells are equal regardless of the boards grown
+    let mut changed = cells.clone();
+    changed.set(Point(2, 2), Cell::Owned(3));
+    changed.set(Point(1, 3), Cell::Owned(0));
+    assert_eq!(vec![Point(2, 2)], changed.owned(3).points());
+    assert_eq!(4, changed.owned_count(0));
+    assert_eq!(1, changed.owned_count(1));
+    assert_eq!(changed.board(|c| c != Cell::Empty), changed.filled());
+    changed.set(Point(2, 2), Cell::Empty);
+    changed.set(Point(1, 3), Cell::Owned(1));
+    assert_eq!(cells, &changed);
+    assert!(changed.owned(3).is_empty());
+
+    let mut board = BitBoard::new(9, 9);
+    assert!(board.is_empty());
+    board.insert(Point(8, 8));
+    board.union_with(&BitBoard::from_points(9, 9, &[Point(0, 0), Point(8, 8)]));
+    assert_eq!(vec![Point(0, 0), Point(8, 8)], board.points());
+}
@@ -0,0 +1,29 @@
+use xcg::coord::{Point, P};
+use xcg::model::Move;
+
+#[test]
+fn test_moves_and_neighbors() {
+    let p = Point(2, 3);
+    for &mv in &Move::ALL {
+        let (di, dj) = mv.delta();
+        assert_eq!(Some(mv), Move::from_delta(di, dj));
+        assert_eq!(Some(mv), p.direction_to(p.next(mv)));
+    }
+    assert_eq!(None, p.direction_to(Point(3, 4)));
+    assert_eq!(vec![Point(2, 4), Point(1, 3), Point(2, 2), Point(3, 3)], p.neighbors().collect::<Vec<_>>());
+    assert!(p.is_inside(3, 4) && !p.is_inside(2, 4) && !Point(-1, 0).is_inside(3, 4));
+    assert_eq!(4, p.manhattan(Point(0, 1)));
+}
+
+#[test]
+fn test_cartesian_conversions() {
+    // the field of 3 rows, the top-left cell is (0, 2) in Cartesian
+    let m = 3;
+    assert_eq!(P(0, 2), Point(0, 0).to_cartesian(m));
+    assert_eq!(Point(2, 4), P(4, 0).to_point(m));
+    // `Up` increases `y`
+    let p = Point(1, 1);
+    assert_eq!(P(1, 2), p.next(Move::Up).to_cartesian(m));
+    assert_eq!(p, p.to_cartesian(m).to_point(m));
+    assert_eq!("(4,0) (2,4)", format!("{:?} {}", P(4, 0), Point(2, 4)));
+}
@@ -0,0 +1,121 @@
+use std::cell::RefCell;
+use rand::IsaacRng;
+
+use xcg::utils::Trim;
+use xcg::model::*;
+use xcg::bot::{KillerBot, RandomBot, TestBot};
+
+#[test]
+fn test_step_events() {
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. a a A . .*.
+        *. . . . . .*.
+        *. . B . . .*.
+        *.*.*.*.*.*.*.
+    "#);
+    // the loop is closed on the border, the tail is captured
+    let mut events = vec![];
+    let mut gs1 = gs.clone();
+    step_with_events(&mut gs1, 0, Move::Up, &mut events);
+    assert_eq!(vec![
+        Event::Moved { player: 0, to: Point(0, 3) },
+        Event::Captured { player: 0, cells: 3 },
+    ], events);
+    // B bites A, A is respawned at its origin
+    let mut events = vec![];
+    let mut gs2 = gs.clone();
+    step_with_events(&mut gs2, 1, Move::Up, &mut events);
+    step_with_events(&mut gs2, 1, Move::Up, &mut events);
+    let at = *gs2.players[0].head().unwrap();
+    assert_eq!(vec![
+        Event::Moved { player: 1, to: Point(2, 2) },
+        Event::Bite { killer: 1, victim: 0, at: Point(1, 2) },
+        Event::Respawn { player: 0, at },
+        Event::Moved { player: 1, to: Point(1, 2) },
+    ], events);
+    // A turns back to its tail, then stands still, then can't leave the field
+    let mut events = vec![];
+    let mut gs3 = gs.clone();
+    step_with_events(&mut gs3, 0, Move::Left, &mut events);
+    let at = *gs3.players[0].head().unwrap();
+    step_with_events(&mut gs3, 0, Move::Stop, &mut events);
+    let mut gs4 = game_state(r#"
+        *.*.*.*.
+        *A . B*.
+        *.*.*.*.
+    "#);
+    step_with_events(&mut gs4, 0, Move::Left, &mut events);
+    assert_eq!(vec![
+        Event::Ouroboros { player: 0, at: Point(1, 2) },
+        Event::Respawn { player: 0, at },
+        Event::Blocked { player: 0 },
+    ], events);
+}
+
+#[test]
+fn test_step_simultaneous_events() {
+    let mut gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. a A . B b*.
+        *. . . . . .*.
+        *.*.*.*.*.*.*.
+    "#);
+    let mut events = vec![];
+    step_simultaneous_with_events(&mut gs, &[Move::Right, Move::Left], &mut events);
+    assert_eq!(vec![Event::HeadToHead { a: 0, b: 1 }], events);
+    let mut gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. a a A . .*.
+        *. B b b b .*.
+        *. . . . . .*.
+        *.*.*.*.*.*.*.
+    "#);
+    let mut events = vec![];
+    step_simultaneous_with_events(&mut gs, &[Move::Down, Move::Up], &mut events);
+    assert_eq!(Event::Bite { killer: 0, victim: 1, at: Point(2, 3) }, events[0]);
+    assert_eq!(Event::Bite { killer: 1, victim: 0, at: Point(1, 1) }, events[1]);
+    assert!(matches!(events[2..], [Event::Respawn { player: 0, .. }, Event::Respawn { player: 1, .. }]));
+}
+
+#[test]
+fn test_match_events_agree_with_stats() {
+    for &rules in &[Rules::Sequential, Rules::Simultaneous] {
+        let mut bots: Vec<Box<dyn Bot>> = vec![
+            Box::new(KillerBot::new(0)),
+            Box::new(RandomBot::new(1)),
+            Box::new(KillerBot::new(2)),
+            Box::new(TestBot::<IsaacRng>::new("rrdd")),
+        ];
+        let names = ["a", "b", "c", "d"];
+        let mut the_match = create_match(12, 14, &names, 300, 0.9, Some(11));
+        the_match.rules = rules;
+        // the border is counted as filled from the start
+        let filled = the_match.game_state.stats.filled_count;
+        let events = RefCell::new(vec![]);
+        let ticks = RefCell::new(0);
+        let replay = run_match_with_events(&mut the_match, &mut bots, &|_, tick_events| {
+            *ticks.borrow_mut() += 1;
+            events.borrow_mut().extend_from_slice(tick_events);
+        });
+        assert_eq!(replay.moves.len(), *ticks.borrow());
+        let events = events.into_inner();
+        let count = |f: &dyn Fn(&Event) -> bool| events.iter().filter(|e| f(e)).count() as u16;
+        let stats = &the_match.game_state.stats;
+        assert_eq!(stats.bite_count, count(&|e| matches!(e, Event::Bite { .. })));
+        assert_eq!(stats.ouroboros_count, count(&|e| matches!(e, Event::Ouroboros { .. })));
+        assert_eq!(stats.head_to_head_count, count(&|e| matches!(e, Event::HeadToHead { .. })));
+        assert_eq!(stats.bite_count + stats.ouroboros_count, count(&|e| matches!(e, Event::Respawn { .. })));
+        let captured: u16 = events.iter().map(|e| match e { Event::Captured { cells, .. } => *cells, _ => 0 }).sum();
+        assert_eq!(stats.filled_count - filled, captured);
+        assert!(captured > 0);
+        // the replay gives the same events
+        let replayed = RefCell::new(vec![]);
+        run_replay_with_events(&replay, &|_, e| replayed.borrow_mut().extend_from_slice(e));
+        assert_eq!(events, replayed.into_inner());
+    }
+}
+
+fn game_state(gs: &str) -> GameState {
+    GameState::parse_string(&gs.trim_indent()).unwrap()
+}
@@ -0,0 +1,145 @@
+use std::thread;
+use std::time::Duration;
+use xcg::utils::Trim;
+use xcg::model::*;
+use xcg::bot::{BotFailure, ExternalBot};
+
+// answers `d` to every `move` command, ignores everything else
+const DOWN_BOT: &str = r#"
+    while read -r cmd rest; do
+        case "$cmd" in
+            move) echo d ;;
+        esac
+    done
+"#;
+
+#[test]
+fn test_external_bot_moves() {
+    let gs = game_state(r#"
+        *A*.*.*.*.*.*.
+        *. . . . . .*.
+        *. . . . . .*.
+        *. . . . . .*.
+        *.*.*.*.*.*.*B
+    "#);
+    let mut gsv = make_view(&gs, 0);
+    let mut bot = ExternalBot::new("sh", &["-c", DOWN_BOT]);
+    bot.reset(&gsv, 0, 42);
+    for _ in 0..3 {
+        make_game_state_view(&mut gsv, &gs, 0);
+        assert_eq!(Move::Down, bot.do_move(&gsv));
+    }
+    assert!(bot.is_alive());
+    assert!(bot.failures().is_empty());
+}
+
+#[test]
+fn test_external_bot_failures() {
+    let gs = game_state(r#"
+        *A*.*.*.*.
+        *. . . .*.
+        *.*.*.*.*B
+    "#);
+    let gsv = make_view(&gs, 0);
+
+    // garbage is reported, but the process keeps running
+    let mut bot = ExternalBot::new("sh", &["-c", "while read -r cmd rest; do [ \"$cmd\" = move ] && echo jump; done"]);
+    bot.reset(&gsv, 0, 0);
+    assert_eq!(Move::Stop, bot.do_move(&gsv));
+    assert_eq!(&[BotFailure::Garbage("jump".to_string())], bot.failures());
+    assert!(bot.is_alive());
+
+    // the process exits immediately, so there is nobody to answer
+    let mut bot = ExternalBot::new("sh", &["-c", "exit 1"]);
+    bot.reset(&gsv, 0, 0);
+    assert_eq!(Move::Stop, bot.do_move(&gsv));
+    assert_eq!(Move::Stop, bot.do_move(&gsv));
+    assert!(!bot.is_alive());
+    assert_eq!(1, bot.failures().len());
+
+    // the process cannot be started at all
+    let mut bot = ExternalBot::new("/nonexistent/xcg-bot", &[] as &[&str]);
+    bot.reset(&gsv, 0, 0);
+    assert_eq!(Move::Stop, bot.do_move(&gsv));
+    match bot.failures() {
+        [BotFailure::Spawn(_)] => {}
+        other => panic!("Unexpected failures: {:?}", other),
+    }
+}
+
+#[test]
+fn test_external_bot_timeout() {
+    let gs = game_state(r#"
+        *A*.*.*.*.
+        *. . . .*.
+        *.*.*.*.*B
+    "#);
+    let gsv = make_view(&gs, 0);
+    // the first answer is late, the others are immediate
+    let script = r#"
+        n=0
+        while read -r cmd rest; do
+            case "$cmd" in
+                move) n=$((n + 1)); [ $n = 1 ] && sleep 0.3 && echo u || echo d ;;
+            esac
+        done
+    "#;
+    let mut bot = ExternalBot::new("sh", &["-c", script]).with_timeout(Duration::from_millis(100));
+    bot.reset(&gsv, 0, 0);
+    assert_eq!(Move::Stop, bot.do_move(&gsv));
+    assert_eq!(&[BotFailure::Timeout], bot.failures());
+    // the late `u` must not be taken as the answer to the next request
+    thread::sleep(Duration::from_millis(400));
+    assert_eq!(Move::Down, bot.do_move(&gsv));
+    assert_eq!(Move::Down, bot.do_move(&gsv));
+    assert_eq!(1, bot.failures().len());
+}
+
+#[test]
+fn test_external_bot_in_match() {
+    let mut bots: [Box<dyn Bot + Send>; 2] = [
+        Box::new(ExternalBot::new("sh", &["-c", DOWN_BOT])),
+        Box::new(ExternalBot::new("sh", &["-c", DOWN_BOT])),
+    ];
+    let names = vec!["A", "B"];
+    let mut the_match = create_match(5, 7, &names, 3, 0.9, None);
+    let logger = |_gs: &GameState| {};
+    let replay = run_timed_match(&mut the_match, &mut bots, &logger);
+    assert_eq!(vec![vec![Move::Down; 2]; 3], replay.moves);
+    // the same with the deadline, the bots come back from the worker threads after the match
+    let mut the_match = create_match(5, 7, &names, 3, 0.9, None);
+    the_match.move_timeout = Some(Duration::from_secs(5));
+    let replay = run_timed_match(&mut the_match, &mut bots, &logger);
+    assert_eq!(vec![vec![Move::Down; 2]; 3], replay.moves);
+    assert_eq!(0, the_match.timings[0].overruns);
+    let replay = run_timed_match(&mut create_match(5, 7, &names, 2, 0.9, None), &mut bots, &logger);
+    assert_eq!(vec![vec![Move::Down; 2]; 2], replay.moves);
+}
+
+#[test]
+fn test_external_bot_move_timeout() {
+    let gs = create_match(5, 7, &["A", "B"], 3, 0.9, None).game_state;
+    let gsv = make_view(&gs, 0);
+    // the match's timeout is taken by the bot without its own one
+    let mut bot = ExternalBot::new("sh", &["-c", "while read -r cmd rest; do :; done"]);
+    bot.set_move_timeout(Duration::from_millis(50));
+    bot.reset(&gsv, 0, 0);
+    assert_eq!(Move::Stop, bot.do_move(&gsv));
+    assert_eq!(&[BotFailure::Timeout], bot.failures());
+}
+
+// === helpers ===
+
+fn game_state(gs: &str) -> GameState {
+    GameState::parse_string(&gs.trim_indent()).unwrap()
+}
+
+fn make_view(gs: &GameState, idx: usize) -> GameStateView {
+    let mut gsv = GameStateView {
+        idx,
+        field: gs.field.clone(),
+        players: gs.players.clone(),
+    };
+    make_game_state_view(&mut gsv, gs, idx);
+    gsv
+}
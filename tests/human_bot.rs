@@ -0,0 +1,48 @@
+use xcg::utils::Trim;
+use xcg::model::*;
+use xcg::bot::{key_to_move, HumanBot};
+use console::Key;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_human_bot_keeps_direction() {
+    let gs = GameState::parse_string(&r#"
+        *.*.*.*.*.*.*.*.
+        *. . . . . . .*.
+        *. a a a A . .*.
+        *. . . . . B .*.
+        *.*.*.*.*.*.*.*.
+    "#.trim_indent()).unwrap();
+    let mut gsv = GameStateView { idx: 0, field: gs.field.clone(), players: gs.players.clone() };
+    make_game_state_view(&mut gsv, &gs, 0);
+    let (tx, rx) = mpsc::channel();
+    let mut bot = HumanBot::new(0, rx).with_pace(Duration::from_millis(20));
+    tx.send(Key::Char('s')).unwrap();
+    bot.reset(&gsv, 0, 1);
+    // the key before the match is dropped
+    assert_eq!(Move::Stop, bot.do_move(&gsv));
+    tx.send(Key::Char('w')).unwrap();
+    assert_eq!(Move::Up, bot.do_move(&gsv));
+    // no key, the tick still lasts the pace
+    let started = Instant::now();
+    assert_eq!(Move::Up, bot.do_move(&gsv));
+    assert!(started.elapsed() >= Duration::from_millis(20));
+    // the last key wins, turning back to the tail is ignored
+    tx.send(Key::ArrowDown).unwrap();
+    tx.send(Key::Char('D')).unwrap();
+    tx.send(Key::ArrowLeft).unwrap();
+    assert_eq!(Move::Right, bot.do_move(&gsv));
+    // nobody at the keyboard
+    drop(tx);
+    assert_eq!(Move::Right, bot.do_move(&gsv));
+}
+
+#[test]
+fn test_key_to_move() {
+    assert_eq!(Some(Move::Left), key_to_move(&Key::ArrowLeft));
+    assert_eq!(Some(Move::Left), key_to_move(&Key::Char('a')));
+    assert_eq!(Some(Move::Down), key_to_move(&Key::Char('S')));
+    assert_eq!(None, key_to_move(&Key::Char('q')));
+    assert_eq!(None, key_to_move(&Key::Enter));
+}
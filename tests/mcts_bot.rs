@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+use xcg::model::*;
+use xcg::bot::{Budget, MctsBot, TestBot};
+use rand::IsaacRng;
+
+fn play_against_idle(budget: Budget, seed: u64) -> Match {
+    let mut bots: Vec<Box<dyn Bot>> = vec![
+        Box::new(MctsBot::new(0, budget).with_depth(16)),
+        Box::new(TestBot::<IsaacRng>::new("s")),
+    ];
+    let mut the_match = create_match(10, 12, &["mcts", "idle"], 80, 0.9, Some(seed));
+    run_match(&mut the_match, &mut bots, &|_| {});
+    the_match
+}
+
+#[test]
+fn test_mcts_bot_is_reproducible() {
+    let m1 = play_against_idle(Budget::Iterations(50), 7);
+    let m2 = play_against_idle(Budget::Iterations(50), 7);
+    assert_eq!(m1.game_state, m2.game_state);
+    // it captures the field while the opponent stands still
+    assert!(m1.game_state.stats.scores[0] > 20);
+    assert_eq!(0, m1.game_state.stats.scores[1]);
+}
+
+#[test]
+fn test_mcts_bot_limited_visibility() {
+    // the hidden border is taken for the empty cells, the simulations must survive it
+    for &visibility in &[Visibility::Radius(1), Visibility::LineOfSight(2)] {
+        let mut bots: Vec<Box<dyn Bot>> = (0..4)
+            .map(|k| Box::new(MctsBot::new(k, Budget::Iterations(30)).with_depth(12)) as Box<dyn Bot>)
+            .collect();
+        let mut the_match = create_match(10, 12, &["A", "B", "C", "D"], 80, 0.9, Some(3));
+        the_match.visibility = visibility;
+        run_match(&mut the_match, &mut bots, &|_| {});
+        assert_eq!(80, the_match.game_state.stats.iteration);
+    }
+}
+
+#[test]
+fn test_mcts_bot_time_budget() {
+    let gs = create_match(16, 20, &["mcts", "killer"], 10, 0.9, Some(1)).game_state;
+    let mut gsv = GameStateView { idx: 0, field: gs.field.clone(), players: gs.players.clone() };
+    make_game_state_view(&mut gsv, &gs, 0);
+    let mut bot = MctsBot::new(0, Budget::Time(Duration::from_millis(20)));
+    bot.reset(&gsv, 0, 42);
+    let started = Instant::now();
+    let mv = bot.do_move(&gsv);
+    // the budget is used up, how much longer the last simulation takes depends on the machine
+    assert!(started.elapsed() >= Duration::from_millis(20));
+    // but a single simulation on this field is far shorter than the slack
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_ne!(Move::Stop, mv);
+}
@@ -3,6 +3,11 @@ use std::borrow::BorrowMut;
 use std::rc::Rc;
 use std::cell::RefCell;
 use core::iter::FromIterator;
+use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
 use rand::prelude::{Rng, RngCore};
 use rand::IsaacRng;
 use rand::SeedableRng;
@@ -10,7 +15,8 @@ use rand::SeedableRng;
 use xcg::utils::Trim;
 use xcg::model::Point;
 use xcg::model::*;
-use xcg::bot::TestBot;
+use xcg::bot::{RandomBot, TestBot};
+use xcg::replay::{read_binary, read_text, write_binary, write_text};
 
 #[test]
 fn test_border() {
@@ -86,6 +92,81 @@ fn test_parse_string() {
     assert_eq!(str0, str1);
 }
 
+#[test]
+fn test_parse_errors() {
+    let parse = |s: &str| GameState::parse_string(&s.trim_indent()).unwrap_err();
+    // rows of the different length
+    let err = parse(r#"
+        *.*.*.*.
+        *. A .*.*.
+        *.*.*.*.
+    "#);
+    assert_eq!(ParseError::RaggedRows { line: 2, column: 1, expected: 8, found: 10 }, err);
+    // the tail is not connected to the head
+    let err = parse(r#"
+        *.*.*.*.*.
+        *. A . a*.
+        *.*.*.*.*.
+    "#);
+    assert_eq!(ParseError::DetachedTail { line: 2, column: 8, player: 'a' }, err);
+    // two heads
+    let err = parse(r#"
+        *A*.*.*A*.
+        *.*.*.*.*.
+    "#);
+    assert_eq!(ParseError::DuplicateHead { line: 1, column: 8, player: 'A' }, err);
+    // unknown cell and the cell of the player who doesn't exist
+    assert_eq!(ParseError::UnknownCell { line: 1, column: 3, ch: '%' }, parse("*A%.*."));
+    assert_eq!(ParseError::UnknownOwner { line: 1, column: 3, owner: 1 }, parse("*A1.*."));
+    // the rest
+    let field = "*A*.*B\n";
+    let err = GameState::parse_string(&format!("{}stats=Stats(1,2,3,4,5,[1])", field)).unwrap_err();
+    assert_eq!(ParseError::ScoreCountMismatch { line: 2, column: 24, expected: 2, found: 1 }, err);
+    let err = GameState::parse_string(&format!("{}stats=Stats(1,2,3,4,99999,[1,2])", field)).unwrap_err();
+    assert_eq!(ParseError::BadNumber { line: 2, column: 21, text: "99999".to_string() }, err);
+    let err = GameState::parse_string(&format!("{}reordering=[1,1]", field)).unwrap_err();
+    assert_eq!(ParseError::BadPermutation { line: 2, column: 12 }, err);
+    let err = GameState::parse_string(&format!("{}reordering=[0, x]", field)).unwrap_err();
+    assert_eq!(ParseError::BadNumber { line: 2, column: 16, text: "x".to_string() }, err);
+    let err = GameState::parse_string(&format!("{}origins=[(0,0)]", field)).unwrap_err();
+    assert_eq!(ParseError::OriginCountMismatch { line: 2, column: 10, expected: 2, found: 1 }, err);
+    let err = GameState::parse_string(&format!("{}  colors=[1,2]", field)).unwrap_err();
+    assert_eq!(ParseError::UnknownKey { line: 2, column: 3, key: "colors".to_string() }, err);
+    assert_eq!("2:3: unknown key 'colors'", err.to_string());
+    let err = GameState::parse_string(&format!("{}stats", field)).unwrap_err();
+    assert_eq!(ParseError::MalformedValue { line: 2, column: 1, key: "stats".to_string() }, err);
+}
+
+#[test]
+fn test_parse_never_panics() {
+    let str0 = r#"
+        *.*.*.*.*A*a*a
+        *.3d2.2.2.0.*a
+        *.2D2.2C2.1.*.
+        *.2.2. . .1B*.
+        *.*.*.*.*.*b*b
+        reordering=[2,1,3,0]
+        stats=Stats(19,33,2,1,0,[1,2,9,1])
+        origins=[(0,6),(4,6),(4,0),(0,0)]
+    "#.trim_indent();
+    let alphabet: Vec<char> = "*. aAbB0123456789=[](),\nxyzÄ".chars().collect();
+    let mut random = IsaacRng::seed_from_u64(42);
+    for _ in 0..500 {
+        let mut chars: Vec<char> = str0.chars().collect();
+        for _ in 0..random.gen_range(1, 4) {
+            let k = random.gen_range(0, chars.len());
+            match random.gen_range(0, 3) {
+                0 => chars[k] = alphabet[random.gen_range(0, alphabet.len())],
+                1 => { chars.remove(k); }
+                _ => chars.insert(k, alphabet[random.gen_range(0, alphabet.len())]),
+            }
+        }
+        let s: String = chars.into_iter().collect();
+        // either result is fine, but no panic
+        let _ = GameState::parse_string(&s);
+    }
+}
+
 #[test]
 fn test_score() {
     let gs = game_state(r#"
@@ -123,6 +204,20 @@ fn test_flood() {
     assert_eq!(vec![Point(1, 1), Point(2, 1), Point(2, 2), Point(2, 3), Point(2, 4), Point(2, 5)], flooded_area)
 }
 
+#[test]
+fn test_flood_long_wall() {
+    // the wall across the whole field splits it into two areas,
+    // the smaller one on the right is captured
+    let field = create_default_field(32, 54);
+    let wall: Vec<Point> = (1..31).map(|i| Point(i, 27)).collect();
+    let flooded = calculate_flood_area(&field, &wall);
+    assert_eq!(25 * 30 + 30, flooded.len());
+    assert!(flooded.iter().all(|p| 27 <= p.1 && p.1 <= 52));
+    // the wall touching the border from one side only captures nothing but itself
+    let wall: Vec<Point> = (1..20).map(|i| Point(i, 27)).collect();
+    assert_eq!(wall, calculate_flood_area(&field, &wall));
+}
+
 #[test]
 fn test_flood_step() {
     let gs0 = game_state(r#"
@@ -185,6 +280,26 @@ fn test_bite_other() {
     assert_eq!(gs_exp, gs1);
 }
 
+#[test]
+fn test_bite_without_border() {
+    // the simulated field with the hidden border has nowhere to respawn, the bitten player is out
+    let mut gs = game_state(r#"
+        #.#.#.#.#.#.
+        #. a A b B#.
+        #.#.#.#.#.#.
+    "#);
+    let mut events = vec![];
+    step_with_events(&mut gs, 0, Move::Right, &mut events);
+    assert_eq!(1, gs.stats.bite_count);
+    assert!(gs.players[1].body().is_empty());
+    assert!(!events.iter().any(|e| match e { Event::Respawn { .. } => true, _ => false }));
+    // the player out of the game doesn't move
+    let before = gs.clone();
+    step(&mut gs, 1, Move::Left);
+    step_simultaneous(&mut gs, &[Move::Stop, Move::Left]);
+    assert_eq!(before, gs);
+}
+
 #[test]
 fn test_bite_self() {
     let gs0 = game_state(r#"
@@ -210,6 +325,233 @@ fn test_bite_self() {
     assert_eq!(gs_exp.to_string(), gs1.to_string());
 }
 
+#[test]
+fn test_simultaneous_step() {
+    let heads = |gs: &GameState| gs.players.iter().map(|p| *p.head().unwrap()).collect::<Vec<_>>();
+    // both enter the same cell
+    let mut gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. a A . B b*.
+        *. . . . . .*.
+        *.*.*.*.*.*.*.
+    "#);
+    step_simultaneous(&mut gs, &[Move::Right, Move::Left]);
+    assert_eq!(vec![Point(1, 2), Point(1, 4)], heads(&gs));
+    assert_eq!(1, gs.stats.head_to_head_count);
+
+    // swap places
+    let mut gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. a A B b .*.
+        *. . . . . .*.
+        *.*.*.*.*.*.*.
+    "#);
+    step_simultaneous(&mut gs, &[Move::Right, Move::Left]);
+    assert_eq!(vec![Point(1, 2), Point(1, 3)], heads(&gs));
+    assert_eq!(1, gs.stats.head_to_head_count);
+
+    // the head is solid even if it leaves the cell
+    step_simultaneous(&mut gs, &[Move::Right, Move::Down]);
+    assert_eq!(vec![Point(1, 2), Point(2, 3)], heads(&gs));
+    assert_eq!(2, gs.stats.head_to_head_count);
+
+    // mutual bites, both die
+    let mut gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. a a A . .*.
+        *. B b b b .*.
+        *. . . . . .*.
+        *.*.*.*.*.*.*.
+    "#);
+    step_simultaneous(&mut gs, &[Move::Down, Move::Up]);
+    assert_eq!(2, gs.stats.bite_count);
+    for k in 0..2 {
+        let Point(i, j) = *gs.players[k].head().unwrap();
+        assert_eq!(1, gs.players[k].body().len());
+        assert_eq!(Cell::Border, gs.field.cells[i as usize][j as usize]);
+    }
+
+    // the bite by the body before the tick, the biter goes on
+    let mut gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. a a A . .*.
+        *. B b b b .*.
+        *. . . . . .*.
+        *.*.*.*.*.*.*.
+    "#);
+    step_simultaneous(&mut gs, &[Move::Down, Move::Down]);
+    assert_eq!(1, gs.stats.bite_count);
+    assert_eq!(vec![Point(1, 1), Point(1, 2), Point(1, 3), Point(2, 3)], *gs.players[0].body());
+    assert_eq!(1, gs.players[1].body().len());
+}
+
+#[test]
+fn test_step_with_undo() {
+    let moves = [Move::Right, Move::Up, Move::Left, Move::Down, Move::Stop];
+    let mut random = IsaacRng::seed_from_u64(17);
+    let mut flooded = 0;
+    for seed in 0..20 {
+        let the_match = create_match(8, 10, &["a", "b", "c"], 1000, 0.9, Some(seed));
+        let mut gs = the_match.game_state;
+        let mut history: Vec<(GameState, Undo)> = vec![];
+        for it in 0..400 {
+            // mostly go forward, sometimes roll back a few steps
+            if !history.is_empty() && random.gen_range(0, 4) == 0 {
+                for _ in 0..random.gen_range(1, history.len() + 1) {
+                    let (before, undo) = history.pop().unwrap();
+                    unstep(&mut gs, undo);
+                    assert_eq!(before, gs, "seed = {}, it = {}", seed, it);
+                }
+            } else {
+                let idx = random.gen_range(0, gs.players.len());
+                let mv = moves[random.gen_range(0, moves.len())];
+                let before = gs.clone();
+                let undo = step_with_undo(&mut gs, idx, mv);
+                // the undoable step does the same as the plain one
+                let mut expected = before.clone();
+                step(&mut expected, idx, mv);
+                assert_eq!(expected, gs);
+                flooded += undo.cells.len();
+                history.push((before, undo));
+            }
+        }
+        while let Some((before, undo)) = history.pop() {
+            unstep(&mut gs, undo);
+            assert_eq!(before, gs);
+        }
+    }
+    // make sure the floods have been reverted too
+    assert!(flooded > 0);
+}
+
+#[test]
+fn test_custom_map() {
+    let map = Field::parse_string(CROSS_MAP).unwrap();
+    assert_eq!((8, 8), (map.m, map.n));
+    assert_eq!(Cell::Void, map.cells[0][0]);
+    assert_eq!(Cell::Border, map.cells[3][4]);
+    assert_eq!(64 - 16, map.playable_count());
+    assert!(Field::parse_string("*.*.\n*.?.\n").is_err());
+    // the owners and the players don't belong to the map
+    let owned = "*.*.*.*.\n*.0a0 *.\n*. A .*.\n*.*.*.*.\n";
+    assert_eq!(Err(ParseError::UnknownCell { line: 2, column: 3, ch: '0' }), Field::parse_string(owned));
+    let headed = "*.*.*.*.\n*.   .*.\n*. A .*.\n*.*.*.*.\n";
+    assert_eq!(Err(ParseError::UnknownCell { line: 3, column: 4, ch: 'A' }), Field::parse_string(headed));
+    assert!(Field::parse_string("*.*.*.\n*.0 *.\n*.*.*.\n").is_err());
+    // the other keys of the saved game state don't matter
+    let keyed = format!("{}reordering=[1,0]\nstats=Stats(0,0,0,0,0,[0])\n", CROSS_MAP);
+    assert_eq!(map, Field::parse_string(&keyed).unwrap());
+
+    // the wide encoding has 3 chars per cell
+    let wide = "encoding=wide\n*..*..*..*..\n*.. ..#..*..\n*..*..*..*..\n";
+    let wide_map = Field::parse_string(wide).unwrap();
+    assert_eq!((3, 4), (wide_map.m, wide_map.n));
+    assert_eq!(Cell::Void, wide_map.cells[1][2]);
+    let wide_owned = "encoding=wide\n*..*..*..*..\n*.. ..0..*..\n*..*0+*..*..\n";
+    assert_eq!(Err(ParseError::UnknownCell { line: 3, column: 7, ch: '0' }), Field::parse_string(wide_owned));
+    let wide_headed = "encoding=wide\n*..*..*..*..\n*.. .. ..*..\n*..*b+*..*..\n";
+    assert_eq!(Err(ParseError::UnknownCell { line: 4, column: 5, ch: 'b' }), Field::parse_string(wide_headed));
+
+    let names = ["A", "B", "C", "D"];
+    let the_match = create_match_on(map.clone(), &names, 100, 0.9, Some(3));
+    let origins = &the_match.game_state.origins;
+    for (k, &Point(i, j)) in origins.iter().enumerate() {
+        assert_eq!(Cell::Border, map.cells[i as usize][j as usize]);
+        assert!(!origins[..k].contains(&Point(i, j)));
+    }
+    // the holes are not the part of the field
+    let mut gs = game_state(&CROSS_MAP.replace("#.#.*.*.*.*.#.#.\n        #.#.*. ", "#.#.*.*.*.*.#.#.\n        #.#.*A "));
+    step(&mut gs, 0, Move::Left);
+    assert_eq!(Point(1, 2), *gs.players[0].head().unwrap());
+    step(&mut gs, 0, Move::Up);
+    assert_eq!(Point(0, 2), *gs.players[0].head().unwrap());
+    step(&mut gs, 0, Move::Left);
+    assert_eq!(Point(0, 2), *gs.players[0].head().unwrap());
+
+    // the match ends when the playable part is filled
+    let random = Rc::new(RefCell::new(IsaacRng::seed_from_u64(5)));
+    let mut bots: Vec<Box<dyn Bot>> = (0..4)
+        .map(|k| Box::new(test_bot_r(k, random.clone(), "ldrurdlu")) as Box<dyn Bot>)
+        .collect();
+    let mut the_match = create_match_on(map.clone(), &names, 1000, 0.9, Some(3));
+    let replay = run_match(&mut the_match, &mut bots, &|_| {});
+    assert_eq!(Some(map.clone()), replay.map);
+    assert_eq!(the_match.game_state, run_replay(&replay, &|_| {}));
+    for row in 0..8 {
+        for col in 0..8 {
+            if map.cells[row][col] == Cell::Void {
+                assert_eq!(Cell::Void, the_match.game_state.field.cells[row][col]);
+            }
+        }
+    }
+}
+
+const CROSS_MAP: &str = r#"
+        #.#.*.*.*.*.#.#.
+        #.#.*. . .*.#.#.
+        *.*.*. . .*.*.*.
+        *. . . .*. . .*.
+        *. . . .*. . .*.
+        *.*.*. . .*.*.*.
+        #.#.*. . .*.#.#.
+        #.#.*.*.*.*.#.#.
+    "#;
+
+#[test]
+fn test_many_players() {
+    for &np in &[12, 64] {
+        let names: Vec<String> = (0..np).map(player_name).collect();
+        let mut bots: Vec<Box<dyn Bot>> = (0..np).map(|k| Box::new(RandomBot::new(k as u8)) as Box<dyn Bot>).collect();
+        let mut the_match = create_match(40, 40, &names, 2, 0.9, Some(np as u64));
+        // short tails only, the tail touching itself is ambiguous in the text
+        run_match(&mut the_match, &mut bots, &|_| {});
+        let gs = &the_match.game_state;
+        let text = gs.to_string();
+        assert_eq!(np > 10, text.contains("encoding=wide"));
+        let parsed = GameState::parse_string(&text).unwrap();
+        assert_eq!(gs.field, parsed.field);
+        assert_eq!(gs.players, parsed.players);
+        assert_eq!(gs.stats, parsed.stats);
+        let pretty = prettify_game_state(gs, false, true);
+        assert!(pretty.contains(&format!("{}: ", names[np - 1])));
+    }
+    // the whole match of more players than the letters, both replays give the final state
+    let np = 30;
+    let names: Vec<String> = (0..np).map(player_name).collect();
+    assert_eq!("P27", names[27]);
+    let mut bots: Vec<Box<dyn Bot>> = (0..np).map(|k| Box::new(RandomBot::new(k as u8)) as Box<dyn Bot>).collect();
+    let mut the_match = create_match(40, 40, &names, 1024, 0.9, Some(np as u64));
+    let replay = run_match(&mut the_match, &mut bots, &|_| {});
+    let gs = &the_match.game_state;
+    // played until the field is filled
+    assert_eq!(gs.stats.iteration as usize, replay.moves.len());
+    assert!(f32::from(gs.stats.filled_count) >= 0.9 * 40.0 * 40.0);
+    let mut text = vec![];
+    write_text(&mut text, &replay).unwrap();
+    assert_eq!(*gs, run_replay(&read_text(&mut &text[..]).unwrap(), &|_| {}));
+    let mut binary = vec![];
+    write_binary(&mut binary, &replay).unwrap();
+    assert_eq!(*gs, run_replay(&read_binary(&mut &binary[..]).unwrap(), &|_| {}));
+    // the owner 11 is `b`, the head of the player 11 is `L`
+    let gs = game_state(r#"
+        *.*.*.*.
+        *.bL . .
+        *.*.*.*A
+    "#);
+    assert_eq!(12, gs.players.len());
+    assert_eq!(Cell::Owned(11), gs.field.cells[1][1]);
+    // the owners, the heads and the tails don't share the glyphs
+    let pretty = prettify_game_state(&gs, false, false);
+    assert!(pretty.contains("\n*  b+ .  . \n*  *  *  0+\n"), "{}", pretty);
+    let gsv = GameStateView { idx: 0, field: gs.field.clone(), players: gs.players.clone() };
+    assert!(gsv.format_string().starts_with("encoding=wide\n*.."));
+    let wide = "*.. ..\n*.. a-\n*.. a+\nencoding=wide";
+    let gs = GameState::parse_string(wide).unwrap();
+    assert_eq!(vec![Point(1, 1), Point(2, 1)], *gs.players[10].body());
+    assert_eq!(ParseError::UnknownCell { line: 2, column: 6, ch: '*' },
+               GameState::parse_string(&wide.replace(" a-", " a*")).unwrap_err());
+}
+
 #[test]
 fn test_game_state_view() {
     let gs = game_state(r#"
@@ -329,6 +671,82 @@ fn test_run_replay() {
     }
 }
 
+#[test]
+fn test_visibility() {
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.*.*.
+        *. A a . . .*. .*.
+        *. . a a . .*. .*.
+        *. . . . . .*. B*.
+        *.*.*.*.*.*.*.*.*.
+    "#);
+    let view = |visibility: Visibility| {
+        let mut gsv = GameStateView {
+            idx: 0,
+            field: gs.field.clone(),
+            players: gs.players.clone(),
+        };
+        make_game_state_view(&mut gsv, &gs, 0);
+        apply_visibility(&mut gsv, visibility);
+        gsv
+    };
+    assert_eq!(gs.field, view(Visibility::Full).field);
+
+    let gsv = view(Visibility::Radius(1));
+    let exp = r#"
+        ?.*.*.?.?.?.?.?.?.
+        *. A a .?.?.?.?.?.
+        ?. . a a .?.?.?.?.
+        ?.?. . .?.?.?.?.?.
+        ?.?.?.?.?.?.?.?.?.
+    "#.trim_indent();
+    assert_eq!(exp, gsv.to_string());
+
+    // the wall in the middle hides B
+    let gsv = view(Visibility::LineOfSight(9));
+    assert_eq!(Cell::Hidden, gsv.field.cells[3][7]);
+    assert_eq!(Cell::Border, gsv.field.cells[3][6]);
+    assert!(gsv.players[1].body().is_empty());
+    let gsv = view(Visibility::Radius(9));
+    assert_eq!(vec![Point(3, 7)], *gsv.players[1].body());
+    // the radius is so large that its square doesn't fit i16
+    let gsv = view(Visibility::Radius(u16::MAX));
+    assert_eq!(gs.field, gsv.field);
+    let gsv = view(Visibility::LineOfSight(300));
+    assert_eq!(Cell::Border, gsv.field.cells[3][6]);
+}
+
+#[test]
+fn test_visibility_hidden_head() {
+    // the tail of B is near A, but the head of B is far
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.*.*.
+        *. A . b b b b B*.
+        *. . . b . . . .*.
+        *. . . . . . . .*.
+        *.*.*.*.*.*.*.*.*.
+    "#);
+    let mut gsv = GameStateView { idx: 0, field: gs.field.clone(), players: gs.players.clone() };
+    make_game_state_view(&mut gsv, &gs, 0);
+    apply_visibility(&mut gsv, Visibility::Radius(2));
+    assert_eq!(Cell::Empty, gsv.field.cells[1][3]);
+    assert_eq!(Cell::Hidden, gsv.field.cells[1][7]);
+    assert!(gsv.players[1].body().is_empty());
+    // the head is seen, the far part of the tail is not
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.*.*.
+        *. A . B b b b b*.
+        *. . . . . . . b*.
+        *. . . . . . . .*.
+        *.*.*.*.*.*.*.*.*.
+    "#);
+    let mut gsv = GameStateView { idx: 0, field: gs.field.clone(), players: gs.players.clone() };
+    make_game_state_view(&mut gsv, &gs, 0);
+    apply_visibility(&mut gsv, Visibility::Radius(3));
+    assert_eq!(vec![Point(1, 4), Point(1, 3)], *gsv.players[1].body());
+    assert_eq!(Some(&Point(1, 3)), gsv.players[1].head());
+}
+
 #[test]
 fn test_run_tournament() {
     let random = Rc::new(RefCell::new(IsaacRng::seed_from_u64(123)));
@@ -370,6 +788,81 @@ fn test_run_tournament() {
     assert_eq!(exp66, rgs66);
 }
 
+#[test]
+fn test_move_timeout() {
+    let mut bots: [Box<dyn Bot>; 2] = [Box::new(SlowBot::new(Move::Down, 20)), Box::new(SlowBot::new(Move::Up, 0))];
+    let names = make_bot_names(&bots);
+    let mut the_match = create_match(7, 7, &names, 4, 0.9, Some(1));
+    the_match.move_timeout = Some(Duration::from_millis(5));
+    let logger = |_gs: &GameState| {};
+    let replay = run_match(&mut the_match, &mut bots, &logger);
+    // the slow bot is always late, so it always stays
+    assert!(replay.moves.iter().all(|ms| ms[0] == Move::Stop && ms[1] == Move::Up));
+    assert_eq!(4, the_match.timings[0].overruns);
+    assert_eq!(0, the_match.timings[1].overruns);
+    assert!(the_match.timings[0].max >= Duration::from_millis(20));
+    assert!(!the_match.timings[0].forfeited);
+}
+
+#[test]
+fn test_timed_match_doesnt_wait() {
+    let (_open, gate) = channel();
+    let answered = Arc::new(AtomicUsize::new(0));
+    let mut bots: [Box<dyn Bot + Send>; 2] = [
+        Box::new(GateBot { gate, answered: answered.clone() }),
+        Box::new(SlowBot::new(Move::Up, 0)),
+    ];
+    let names = make_bot_names(&bots);
+    let mut the_match = create_match(7, 7, &names, 4, 0.9, Some(1));
+    the_match.move_timeout = Some(Duration::from_millis(5));
+    let replay = run_timed_match(&mut the_match, &mut bots, &|_| {});
+    // the match is over while the bot is still busy with its first move, late or busy it stays
+    assert_eq!(0, answered.load(Ordering::SeqCst));
+    assert!(replay.moves.iter().all(|ms| ms[0] == Move::Stop && ms[1] == Move::Up));
+    assert_eq!(4, the_match.timings[0].overruns);
+    assert_eq!(0, the_match.timings[1].overruns);
+    assert!(the_match.timings[0].max >= Duration::from_millis(5));
+    assert!(!the_match.timings[0].forfeited);
+}
+
+#[test]
+fn test_overrun_and_forfeit() {
+    // the first move is both late and exhausts the budget
+    let (_open, gate) = channel();
+    let answered = Arc::new(AtomicUsize::new(0));
+    let mut bots: [Box<dyn Bot + Send>; 2] = [
+        Box::new(GateBot { gate, answered: answered.clone() }),
+        Box::new(SlowBot::new(Move::Up, 0)),
+    ];
+    let names = make_bot_names(&bots);
+    let mut the_match = create_match(7, 7, &names, 3, 0.9, Some(1));
+    the_match.move_timeout = Some(Duration::from_millis(5));
+    the_match.time_budget = Some(Duration::from_millis(5));
+    run_timed_match(&mut the_match, &mut bots, &|_| {});
+    assert_eq!(0, answered.load(Ordering::SeqCst));
+    assert_eq!(1, the_match.timings[0].overruns);
+    assert!(the_match.timings[0].forfeited);
+}
+
+#[test]
+fn test_time_budget() {
+    let mut bots: [Box<dyn Bot>; 2] = [Box::new(SlowBot::new(Move::Down, 50)), Box::new(SlowBot::new(Move::Up, 0))];
+    let names = make_bot_names(&bots);
+    let mut the_match = create_match(7, 7, &names, 6, 0.9, Some(1));
+    the_match.time_budget = Some(Duration::from_millis(125));
+    let logger = |_gs: &GameState| {};
+    let replay = run_match(&mut the_match, &mut bots, &logger);
+    // the third move exceeds the budget for sure, the first one fits it unless it takes 75ms more,
+    // after the budget is exhausted the bot stays forever
+    let forfeit_tick = replay.moves.iter().position(|ms| ms[0] == Move::Stop).unwrap();
+    assert!(1 <= forfeit_tick && forfeit_tick <= 2);
+    assert!(replay.moves[forfeit_tick..].iter().all(|ms| ms[0] == Move::Stop));
+    assert!(the_match.timings[0].forfeited);
+    assert!(!the_match.timings[1].forfeited);
+    // and the replay is still consistent with the match
+    assert_eq!(the_match.game_state, run_replay(&replay, &logger));
+}
+
 fn make_bot_names<T>(bots: &[T]) -> Vec<String> {
     let mut names = vec![];
     for k in 0..bots.len() {
@@ -399,6 +892,44 @@ fn test_bot_r<R: Rng>(idx: usize, rng: Rc<RefCell<R>>, path: &str) -> TestBot<R>
     TestBot::with_index_random(path, idx, rng)
 }
 
+/// always does the same move, but thinks `delay` milliseconds before that
+struct SlowBot {
+    mv: Move,
+    delay: u64,
+}
+
+impl SlowBot {
+    fn new(mv: Move, delay: u64) -> SlowBot {
+        SlowBot { mv, delay }
+    }
+}
+
+impl Bot for SlowBot {
+    fn reset(&mut self, _gs: &GameStateView, _idx: usize, _seed: u64) {}
+
+    fn do_move(&mut self, _gs: &GameStateView) -> Move {
+        thread::sleep(Duration::from_millis(self.delay));
+        self.mv
+    }
+}
+
+/// waits for the gate that is never opened, counts the moves made
+struct GateBot {
+    gate: Receiver<()>,
+    answered: Arc<AtomicUsize>,
+}
+
+impl Bot for GateBot {
+    fn reset(&mut self, _gs: &GameStateView, _idx: usize, _seed: u64) {}
+
+    fn do_move(&mut self, _gs: &GameStateView) -> Move {
+        // the match that waits for the bot gets the move after all, and the test fails
+        let _ = self.gate.recv_timeout(Duration::from_secs(10));
+        self.answered.fetch_add(1, Ordering::SeqCst);
+        Move::Down
+    }
+}
+
 fn play<B: Bot>(gs: &GameState, bots: &mut [B]) -> GameState {
     let nb = bots.len();
     let mut gs = gs.clone();
@@ -0,0 +1,56 @@
+use xcg::utils::Trim;
+use xcg::model::*;
+use xcg::bot::{ParanoidBot, TestBot};
+use rand::IsaacRng;
+
+#[test]
+fn test_paranoid_bot_closes_exposed_loop() {
+    // the enemy is going to bite the tail, the bot must return home at once
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.*.
+        *. . . . . . .*.
+        *. a a a A . .*.
+        *. a . . . B .*.
+        *. a . . . . .*.
+        *.*.*.*.*.*.*.*.
+    "#);
+    let mut bot = ParanoidBot::new(0, 3);
+    let gsv = make_view(&gs, 0);
+    bot.reset(&gsv, 0, 1);
+    assert_eq!(Move::Up, bot.do_move(&gsv));
+}
+
+#[test]
+fn test_paranoid_bot_captures() {
+    let mut bots: Vec<Box<dyn Bot>> = vec![
+        Box::new(ParanoidBot::new(0, 3)),
+        Box::new(TestBot::<IsaacRng>::new("s")),
+    ];
+    let mut the_match = create_match(10, 12, &["paranoid", "idle"], 100, 0.9, Some(3));
+    run_match(&mut the_match, &mut bots, &|_| {});
+    let stats = &the_match.game_state.stats;
+    assert!(stats.scores[0] > 40, "{:?}", stats);
+    assert_eq!(0, stats.ouroboros_count);
+}
+
+fn game_state(gs: &str) -> GameState {
+    GameState::parse_string(&gs.trim_indent()).unwrap()
+}
+
+fn make_view(gs: &GameState, idx: usize) -> GameStateView {
+    let mut gsv = GameStateView { idx, field: gs.field.clone(), players: gs.players.clone() };
+    make_game_state_view(&mut gsv, gs, idx);
+    gsv
+}
+
+#[test]
+fn test_paranoid_bot_limited_visibility() {
+    // the hidden border is not seen, but the simulated players must still have where to respawn
+    for &visibility in &[Visibility::Radius(1), Visibility::LineOfSight(2)] {
+        let mut bots: Vec<Box<dyn Bot>> = (0..4).map(|k| Box::new(ParanoidBot::new(k, 2)) as Box<dyn Bot>).collect();
+        let mut the_match = create_match(12, 14, &["A", "B", "C", "D"], 200, 0.9, Some(5));
+        the_match.visibility = visibility;
+        run_match(&mut the_match, &mut bots, &|_| {});
+        assert_eq!(200, the_match.game_state.stats.iteration);
+    }
+}
@@ -4,8 +4,9 @@ use std::collections::HashMap;
 use xcg::utils::Trim;
 use xcg::model::*;
 use xcg::bot::common::Weight;
-use xcg::bot::common::{P, a_star_find};
+use xcg::bot::common::a_star_find;
 use xcg::bot::common::distance;
+use xcg::bot::path_find::{find_nearest, find_path, find_path_to_any, FieldGrid};
 
 #[test]
 fn test_a_star() {
@@ -21,19 +22,16 @@ fn test_a_star() {
             *.*.*.*.*.*.*.*.*.*.*.
         "#);
 
-    let m = gs.field.m as i16;
-    let n = gs.field.n as i16;
-    let me = gs.players[0].body().iter().map(|p| P(p.1, m - 1 - p.0)).collect::<Vec<P>>();
-    let is_boundary = |p: &P| {
-        let P(x, y) = *p;
-        0 <= y && y < m && 0 <= x && x < n && !me.contains(&p)
-    };
-    let heuristic = |p: &P, q: &P| distance(p, q);
+    let m = gs.field.m;
+    let n = gs.field.n;
+    let me = gs.players[0].body().clone();
+    let is_boundary = |p: &Point| p.is_inside(m, n) && !me.contains(&p);
+    let heuristic = |p: &Point, q: &Point| distance(p, q);
 
-    // we are using decartes coordinates, src -> dst
+    // src -> dst
     let pairs = [
-        (P(5, 4), P(9, 2)),
-        (P(5, 4), P(8, 3))
+        (Point(4, 5), Point(6, 9)),
+        (Point(4, 5), Point(5, 8))
     ];
 
     let mut gs_paths: Vec<String> = vec![];
@@ -42,44 +40,36 @@ fn test_a_star() {
         for i in 0..gs.field.m {
             for j in 0..gs.field.n {
                 match gs.field.cells[i][j] {
-                    Cell::Owned(_) => gs.field.cells[i][j] = Cell::Empty,
+                    Cell::Owned(_) => gs.field.cells.set(Point(i as i16, j as i16), Cell::Empty),
                     _ => (),
                 }
             }
         }
         let path = {
             // use this logger for the debugging
-            let mut _logger = Some(|ol: &PriorityQueue<P, Weight>, cl: &HashMap<P, P>| {
-                for (k, _) in ol {
-                    let P(x, y) = *k;
-                    let j = x as usize;
-                    let i = (m as usize) - 1 - (y as usize);
-                    gs.field.cells[i][j] = Cell::Owned(0);
+            let mut _logger = Some(|ol: &PriorityQueue<Point, Weight>, cl: &HashMap<Point, Point>| {
+                for (&p, _) in ol {
+                    gs.field.cells.set(p, Cell::Owned(0));
                 }
-                for (p, _) in cl {
-                    let P(x, y) = *p;
-                    let j = x as usize;
-                    let i = (m as usize) - 1 - (y as usize);
-                    gs.field.cells[i][j] = Cell::Owned(1);
+                for (&p, _) in cl {
+                    gs.field.cells.set(p, Cell::Owned(1));
                 }
                 println!("{}", prettify_game_state(&gs, false, false));
                 println!("{:?}", ol);
             });
-            let logger: Option<fn(&PriorityQueue<P, Weight>, &HashMap<P, P>)> = None;
+            let logger: Option<fn(&PriorityQueue<Point, Weight>, &HashMap<Point, Point>)> = None;
             a_star_find(&src, &dst, is_boundary, heuristic, logger)
         };
 
         if let Some(path) = path {
             println!("path = {:?}", path);
-            for P(x, y) in path {
-                let j = x as usize;
-                let i = (m as usize) - 1 - (y as usize);
-                gs.field.cells[i][j] = Cell::Owned(2);
+            for p in path {
+                gs.field.cells.set(p, Cell::Owned(2));
             }
             gs_paths.push(prettify_game_state(&gs, false, false));
         }
     }
-    // (5,4) -> (9,2)
+    // (4,5) -> (6,9)
     let exp0 = r#"
         * * * * * * * * * * *
         * . . . . . . . . . *
@@ -94,7 +84,7 @@ fn test_a_star() {
     let exp0 = format!("A: 0                 \n{}\niteration: 0\n", exp0);
     assert_eq!(exp0, gs_paths[0]);
 
-    // (5,4) -> (9,2)
+    // (4,5) -> (5,8)
     let exp1 = r#"
         * * * * * * * * * * *
         * . . . . . . . . . *
@@ -121,23 +111,61 @@ fn test_a_cannot_find() {
         *. . . . . . . . .*.
         *.*.*.*.*.*.*.*.*.*.
     "#);
-    let m = gs.field.m as i16;
-    let n = gs.field.n as i16;
-    let me = gs.players[0].body().iter().map(|p| P(p.1, m - 1 - p.0)).collect::<Vec<P>>();
-    let is_boundary = |p: &P| {
-        let P(x, y) = *p;
-        0 <= y && y < m && 0 <= x && x < n && !me.contains(&p)
-    };
-    let heuristic = |p: &P, q: &P| distance(p, q);
-    // we are using decartes coordinates, src -> dst
-    let src = P(4, 3);
-    let dst = P(8, 1);
-    let logger: Option<fn(&PriorityQueue<P, Weight>, &HashMap<P, P>)> = None;
+    let m = gs.field.m;
+    let n = gs.field.n;
+    let me = gs.players[0].body().clone();
+    let is_boundary = |p: &Point| p.is_inside(m, n) && !me.contains(&p);
+    let heuristic = |p: &Point, q: &Point| distance(p, q);
+    let src = Point(3, 4);
+    let dst = Point(5, 8);
+    let logger: Option<fn(&PriorityQueue<Point, Weight>, &HashMap<Point, Point>)> = None;
     let path = a_star_find(&src, &dst, is_boundary, heuristic, logger);
     assert_eq!(path, None);
 }
 
 
+#[test]
+fn test_find_path_weighted() {
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.*.
+        *. . . . . . .*.
+        *. .0 0 0 0  .*.
+        *. . . . . . .*.
+        *A*.*.*.*.*.*.*.
+    "#);
+    let field = &gs.field;
+    let grid = FieldGrid::new(field);
+    let path = find_path(&grid, Point(1, 1), Point(1, 6), None).unwrap();
+    assert_eq!(5, path.cost);
+    assert!(path.nodes.iter().all(|p| p.0 == 1));
+    // extending the tail is expensive, so go along the border
+    let grid = FieldGrid::new(field).with_empty_cost(3);
+    let path = find_path(&grid, Point(1, 1), Point(1, 6), None).unwrap();
+    assert_eq!(9, path.cost);
+    assert!(path.nodes[1..path.nodes.len() - 1].iter().all(|p| p.0 == 0));
+    assert_eq!(None, find_path(&grid, Point(1, 1), Point(3, 6), Some(3)));
+}
+
+#[test]
+fn test_find_nearest_owned() {
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.*.
+        *. . . . . . .*.
+        *. .0 0 0 0  .*.
+        *. . . . . . .*.
+        *A*.*.*.*.*.*.*.
+    "#);
+    let field = &gs.field;
+    let grid = FieldGrid::new(field);
+    // two paths of the same length, the tie is broken by the order of the points
+    let is_owned = |p: Point| field.cells.get(p) == Cell::Owned(0);
+    let path = find_nearest(&grid, Point(3, 6), is_owned, None).unwrap();
+    assert_eq!(vec![Point(3, 6), Point(2, 6), Point(2, 5)], path.nodes);
+    assert_eq!(path, find_nearest(&grid, Point(3, 6), is_owned, None).unwrap());
+    let path = find_path_to_any(&grid, Point(2, 1), &[Point(3, 6), Point(1, 3)], None).unwrap();
+    assert_eq!((Point(1, 3), 3), (*path.nodes.last().unwrap(), path.cost));
+}
+
 fn game_state(gs: &str) -> GameState {
     GameState::parse_string(&gs.trim_indent()).unwrap()
 }
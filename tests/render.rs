@@ -0,0 +1,79 @@
+use xcg::model::*;
+use xcg::render::*;
+use xcg::utils::Trim;
+use std::time::Duration;
+
+#[test]
+fn test_render_svg() {
+    let gs = GameState::parse_string(&r#"
+        *.*.*.*.*.
+        *.0  a A*.
+        *. . . B*.
+        *.*.*.*.*.
+    "#.trim_indent()).unwrap();
+    let svg = render_svg(&gs);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"60\" height=\"84\""));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    // 14 border cells and the owned one, the tail and 2 heads
+    assert_eq!(14, svg.matches(r##"fill="#888888""##).count());
+    assert_eq!(1, svg.matches(r##"fill="#cc3333" fill-opacity="0.45""##).count());
+    assert_eq!(2, svg.matches(r#"stroke="black""#).count());
+    assert!(svg.contains(">A</text>") && svg.contains(">B</text>"));
+    assert_eq!("#cc3333", player_color(0));
+    assert_ne!(player_color(20), player_color(21));
+}
+
+#[test]
+fn test_render_html() {
+    let replay = Replay {
+        height: 5,
+        width: 7,
+        duration: 20,
+        ratio: 0.9,
+        player_names: vec!["<A>".to_string(), "B".to_string()],
+        moves: vec![vec![Move::Up, Move::Left], vec![Move::Left, Move::Stop]],
+        random_seed: Some(69),
+        rules: Rules::Sequential,
+        map: None,
+    };
+    let frames = replay_frames(&replay);
+    assert_eq!(3, frames.len());
+    assert_eq!(run_replay(&replay, &|_| {}), frames[2]);
+    let html = render_html(&replay);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    // the state before the first tick and after each of 2 ticks
+    assert_eq!(3, html.matches(r#"{"cells":"#).count());
+    // the names can't close the script
+    assert!(html.contains(r#""names":["\u003cA>","B"]"#));
+    assert!(html.contains(r#"<svg id="chart""#) && html.contains(r#"<input id="timeline""#));
+    assert!(!html.contains("{{"));
+}
+
+#[test]
+fn test_write_asciicast() {
+    let replay = Replay {
+        height: 5,
+        width: 7,
+        duration: 20,
+        ratio: 0.9,
+        player_names: vec!["A".to_string(), "B".to_string()],
+        moves: vec![vec![Move::Up, Move::Left], vec![Move::Left, Move::Stop]],
+        random_seed: Some(69),
+        rules: Rules::Sequential,
+        map: None,
+    };
+    let mut buf = vec![];
+    write_asciicast(&mut buf, &replay, Duration::from_millis(250), false).unwrap();
+    let cast = String::from_utf8(buf).unwrap();
+    let lines: Vec<&str> = cast.lines().collect();
+    assert_eq!(r#"{"version":2,"width":13,"height":9,"title":"A vs B"}"#, lines[0]);
+    assert_eq!(4, lines.len());
+    assert!(lines[1].starts_with(r#"[0.000000, "o", "\u001b[2J\u001b[HA: 0 "#));
+    assert!(lines[3].starts_with(r#"[0.500000, "o", "\u001b[HA: "#));
+    assert!(lines[3].contains(r#"\u000d\u000a* * * * * * *\u000d\u000a"#));
+    assert!(lines[3].ends_with(r#"iteration: 2\u000d\u000a"]"#));
+    // the colors are there even if the output is not a terminal
+    let mut buf = vec![];
+    write_asciicast(&mut buf, &replay, Duration::from_millis(250), true).unwrap();
+    assert!(String::from_utf8(buf).unwrap().contains("\\u001b[37m"));
+}
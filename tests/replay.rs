@@ -0,0 +1,231 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::rc::Rc;
+use rand::prelude::RngCore;
+use rand::IsaacRng;
+use rand::SeedableRng;
+
+use xcg::model::*;
+use xcg::replay::*;
+use xcg::bot::TestBot;
+
+#[test]
+fn test_text_round_trip() {
+    let replay = Replay {
+        height: 5,
+        width: 7,
+        duration: 20,
+        ratio: 0.9,
+        player_names: vec!["A".to_string(), "B".to_string()],
+        moves: vec![vec![Move::Down, Move::Left], vec![Move::Left, Move::Stop]],
+        random_seed: Some(69),
+        rules: Rules::Sequential,
+        map: None,
+    };
+    let mut buf = vec![];
+    write_text(&mut buf, &replay).unwrap();
+    let exp = "xcg-replay 3\nheight=5\nwidth=7\nduration=20\nratio=0.9\nrandom_seed=69\nplayer=A\nplayer=B\nmoves=\ndl\nls\n";
+    assert_eq!(exp, String::from_utf8(buf.clone()).unwrap());
+    assert_eq!(replay, read_text(&mut &buf[..]).unwrap());
+}
+
+#[test]
+fn test_saved_replay_reproduces_match() {
+    let random = Rc::new(RefCell::new(IsaacRng::seed_from_u64(123)));
+    let a = TestBot::with_index_random("dllll", 0, random.clone());
+    let b = TestBot::with_index_random("luuuu", 1, random.clone());
+    let c = TestBot::with_index_random("urrrr", 2, random.clone());
+    let mut bots: [Box<dyn Bot>; 3] = [Box::new(a), Box::new(b), Box::new(c)];
+    let names = vec!["A", "B", "C"];
+    let logger = |_gs: &GameState| {};
+    let dir = env::temp_dir();
+    for k in 0..20 {
+        let seed = (*random).borrow_mut().next_u64();
+        let mut the_match = create_match(11, 13, &names, 64, 0.9, Some(seed));
+        let replay = run_match(&mut the_match, &mut bots, &logger);
+        for &binary in &[false, true] {
+            let path = dir.join(format!("xcg-replay-test-{}-{}-{}", std::process::id(), k, binary));
+            save_replay(&path, &replay, binary).unwrap();
+            let loaded = load_replay(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(replay, loaded);
+            assert_eq!(the_match.game_state, run_replay(&loaded, &logger));
+        }
+    }
+}
+
+#[test]
+fn test_simultaneous_replay() {
+    let random = Rc::new(RefCell::new(IsaacRng::seed_from_u64(7)));
+    let mut bots: Vec<Box<dyn Bot>> = (0..4)
+        .map(|k| Box::new(TestBot::with_index_random("ldru", k, random.clone())) as Box<dyn Bot>)
+        .collect();
+    let names = vec!["A", "B", "C", "D"];
+    let logger = |_gs: &GameState| {};
+    let mut the_match = create_match(9, 9, &names, 100, 0.9, Some(5));
+    the_match.rules = Rules::Simultaneous;
+    let replay = run_match(&mut the_match, &mut bots, &logger);
+    let mut buf = vec![];
+    write_text(&mut buf, &replay).unwrap();
+    assert!(String::from_utf8(buf.clone()).unwrap().contains("\nrules=simultaneous\n"));
+    let loaded = read_text(&mut &buf[..]).unwrap();
+    assert_eq!(replay, loaded);
+    assert_eq!(the_match.game_state, run_replay(&loaded, &logger));
+    let mut buf = vec![];
+    write_binary(&mut buf, &replay).unwrap();
+    assert_eq!(replay, read_binary(&mut &buf[..]).unwrap());
+}
+
+#[test]
+fn test_map_round_trip() {
+    let map = Field::parse_string("
+        #.*.*.*.
+        *.*. .*.
+        *. . .*.
+        *.*.*.*.
+    ").unwrap();
+    let replay = Replay {
+        height: 4,
+        width: 4,
+        duration: 10,
+        ratio: 0.9,
+        player_names: vec!["A".to_string()],
+        moves: vec![vec![Move::Down]],
+        random_seed: None,
+        rules: Rules::Sequential,
+        map: Some(map),
+    };
+    let mut buf = vec![];
+    write_text(&mut buf, &replay).unwrap();
+    assert!(String::from_utf8(buf.clone()).unwrap().contains("map=#***\nmap=**.*\nmap=*..*\nmap=****\n"));
+    assert_eq!(replay, read_text(&mut &buf[..]).unwrap());
+    let mut buf = vec![];
+    write_binary(&mut buf, &replay).unwrap();
+    assert_eq!(replay, read_binary(&mut &buf[..]).unwrap());
+}
+
+#[test]
+fn test_binary_is_compact() {
+    let replay = Replay {
+        height: 32,
+        width: 54,
+        duration: 1024,
+        ratio: 0.95,
+        player_names: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+        moves: vec![vec![Move::Right, Move::Up, Move::Stop]; 1000],
+        random_seed: None,
+        rules: Rules::Simultaneous,
+        map: None,
+    };
+    let mut buf = vec![];
+    write_binary(&mut buf, &replay).unwrap();
+    // 2 bytes per tick for 3 players
+    assert!(buf.len() < 2 * 1000 + 64);
+    assert_eq!(replay, read_binary(&mut &buf[..]).unwrap());
+}
+
+#[test]
+fn test_invalid_replays() {
+    match read_text(&mut &b"hello\n"[..]) {
+        Err(ReplayError::BadMagic) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    match read_text(&mut &b"xcg-replay 99\n"[..]) {
+        Err(ReplayError::UnsupportedVersion(99)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    let text = "xcg-replay 1\nheight=5\nwidth=7\nduration=20\nratio=0.9\nplayer=A\nmoves=\nx\n";
+    match read_text(&mut text.as_bytes()) {
+        Err(ReplayError::Malformed { line: 8, .. }) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    // truncated binary
+    match read_binary(&mut &b"XCGR\x01\x00\x05"[..]) {
+        Err(ReplayError::Io(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_invalid_binary_replays() {
+    let replay = Replay {
+        height: 5,
+        width: 7,
+        duration: 20,
+        ratio: 0.9,
+        player_names: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+        moves: vec![vec![Move::Right, Move::Up, Move::Stop]; 2],
+        random_seed: None,
+        rules: Rules::Sequential,
+        map: None,
+    };
+    let mut buf = vec![];
+    write_binary(&mut buf, &replay).unwrap();
+    // the moves are at the end, 2 bytes per tick
+    let n = buf.len();
+    buf[n - 1] = 0x0F;
+    match read_binary(&mut &buf[..]) {
+        Err(ReplayError::MalformedBinary { offset, .. }) => assert_eq!(n - 1, offset),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    // the huge ticks count is not trusted
+    buf.truncate(n - 4);
+    buf[n - 8..n - 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    match read_binary(&mut &buf[..]) {
+        Err(ReplayError::Io(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    let too_wide = Replay { width: 70000, ..replay.clone() };
+    let err = write_binary(&mut vec![], &too_wide).unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    let too_many = Replay { player_names: vec!["A".to_string(); 256], ..replay };
+    assert!(write_binary(&mut vec![], &too_many).is_err());
+}
+
+#[test]
+fn test_invalid_replay_headers() {
+    let replay = Replay {
+        height: 5,
+        width: 7,
+        duration: 20,
+        ratio: 0.9,
+        player_names: vec!["A".to_string(), "B".to_string()],
+        moves: vec![vec![Move::Right, Move::Up]; 2],
+        random_seed: None,
+        rules: Rules::Sequential,
+        map: None,
+    };
+    let mut buf = vec![];
+    write_text(&mut buf, &replay).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    let expect_line = |text: &str, expected: usize| match read_text(&mut text.as_bytes()) {
+        Err(ReplayError::Malformed { line, .. }) => assert_eq!(expected, line, "{}", text),
+        other => panic!("Unexpected result: {:?}", other),
+    };
+    expect_line(&text.replace("height=5", "height=0"), 2);
+    expect_line(&text.replace("width=7", "width=1"), 3);
+    expect_line(&text.replace("player=A\nplayer=B\n", ""), 6);
+    let many: String = (0..=MAX_PLAYERS).map(|k| format!("player={}\n", k)).collect();
+    expect_line(&text.replace("player=A\nplayer=B\n", &many), 6 + MAX_PLAYERS);
+    expect_line(&text.replace("\nru\n", "\nr\n"), 9);
+
+    let mut buf = vec![];
+    write_binary(&mut buf, &replay).unwrap();
+    // magic, version, height at 6, width at 8, then 13 bytes to the players count at 21
+    let expect_offset = |patch: &dyn Fn(&mut Vec<u8>), expected: usize| {
+        let mut bytes = buf.clone();
+        patch(&mut bytes);
+        match read_binary(&mut &bytes[..]) {
+            Err(ReplayError::MalformedBinary { offset, .. }) => assert_eq!(expected, offset),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    };
+    expect_offset(&|b| b[6..8].copy_from_slice(&0u16.to_le_bytes()), 6);
+    expect_offset(&|b| b[8..10].copy_from_slice(&1u16.to_le_bytes()), 8);
+    expect_offset(&|b| b[21] = 0, 21);
+    expect_offset(&|b| b[21] = MAX_PLAYERS as u8 + 1, 21);
+    let ragged = Replay { moves: vec![vec![Move::Right]], ..replay };
+    assert!(write_binary(&mut vec![], &ragged).is_err());
+}
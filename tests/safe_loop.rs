@@ -0,0 +1,59 @@
+use xcg::utils::Trim;
+use xcg::model::*;
+use xcg::bot::common::{candidate_loops, plan_safe_loop};
+
+#[test]
+fn test_plan_safe_loop_returns_before_bite() {
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.*.
+        *. . . . . . .*.
+        *. a a a A . .*.
+        *. a . . . . .*.
+        *. a . . . . .*.
+        *. . . . . B .*.
+        *. . . . . . .*.
+        *.*.*.*.*.*.*.*.
+    "#);
+    let gsv = make_view(&gs, 0);
+    // the enemy needs 4 moves to bite, the bigger loops are too risky
+    let candidates = candidate_loops(&gsv, 0, 4);
+    assert!(candidates.iter().any(|lp| !lp.is_safe() && lp.area > 10));
+    let best = plan_safe_loop(&gsv, 0, 4).unwrap();
+    assert_eq!(vec![Point(1, 4), Point(0, 4)], best.path);
+    assert_eq!((10, 2, 4), (best.area, best.moves, best.enemy_moves));
+    assert_eq!(Move::Up, best.next_move(Point(2, 4)));
+}
+
+#[test]
+fn test_plan_safe_loop_from_home() {
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.*.*.*.
+        *. . . . . . . . .*.
+        *. . . . . . . . .*.
+        *. . . . . . . . .*.
+        *. . . . . . . . .*.
+        *. . . . . . . . B*.
+        *. . . . . . . . .*.
+        *.*.*.*.*.*A*.*.*.*.
+    "#);
+    let gsv = make_view(&gs, 0);
+    let best = plan_safe_loop(&gsv, 0, 3).unwrap();
+    assert!(best.is_safe() && best.area > 0);
+    assert_eq!(best.path.len(), best.moves);
+    let home = *best.path.last().unwrap();
+    assert_ne!(Cell::Empty, gs.field.cells.get(home));
+    assert!(best.path[..best.moves - 1].iter().all(|&p| gs.field.cells.get(p) == Cell::Empty));
+    // the enemy is on the right, so the loop goes to the left
+    assert_eq!(vec![Point(6, 5), Point(6, 4), Point(7, 4)], best.path);
+    assert_eq!(Move::Up, best.next_move(Point(7, 5)));
+}
+
+fn game_state(gs: &str) -> GameState {
+    GameState::parse_string(&gs.trim_indent()).unwrap()
+}
+
+fn make_view(gs: &GameState, idx: usize) -> GameStateView {
+    let mut gsv = GameStateView { idx, field: gs.field.clone(), players: gs.players.clone() };
+    make_game_state_view(&mut gsv, gs, idx);
+    gsv
+}
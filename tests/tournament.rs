@@ -0,0 +1,114 @@
+use xcg::model::Rules;
+use xcg::tournament::*;
+use std::collections::HashSet;
+use xcg::bot::{KillerBot, TestBot};
+use rand::IsaacRng;
+
+fn settings(players: usize, pairing: Pairing) -> Settings {
+    Settings {
+        height: 9,
+        width: 11,
+        duration: 64,
+        ratio: 0.9,
+        players,
+        matches: 2,
+        rotate: true,
+        pairing,
+        seed: 42,
+        map: None,
+        rules: Rules::Sequential,
+        workers: 1,
+    }
+}
+
+fn roster() -> Vec<Entry> {
+    // the idle bot only stands still, the killer bot must beat it
+    let idle: BotFactory = Box::new(|_| Box::new(TestBot::<IsaacRng>::new("s")));
+    let killer: BotFactory = Box::new(|k| Box::new(KillerBot::new(k)));
+    vec![
+        Entry { name: "idle".to_string(), factory: idle },
+        Entry { name: "killer".to_string(), factory: killer },
+    ]
+}
+
+#[test]
+fn test_schedules() {
+    assert_eq!(10, round_robin(5, 2).len());
+    assert_eq!(vec![vec![0, 1, 2], vec![0, 1, 3], vec![0, 2, 3], vec![1, 2, 3]], round_robin(4, 3));
+    assert!(round_robin(2, 3).is_empty());
+    assert_eq!(vec![vec![3, 5, 7], vec![5, 7, 3], vec![7, 3, 5]], rotations(&[3, 5, 7]));
+    // the one left out sits out the round
+    let ratings = [1500.0, 1400.0, 1600.0, 1450.0, 1300.0];
+    assert_eq!(vec![vec![2, 0], vec![3, 1]], swiss_round(&ratings, 2, &HashSet::new()));
+    // the groups that have met are avoided, the sorted groups are remembered
+    let played: HashSet<Vec<usize>> = vec![vec![0, 2], vec![1, 3]].into_iter().collect();
+    assert_eq!(vec![vec![2, 3], vec![0, 1]], swiss_round(&ratings, 2, &played));
+    // the repeat is the only choice left
+    let played: HashSet<Vec<usize>> = vec![vec![0, 1]].into_iter().collect();
+    assert_eq!(vec![vec![0, 1]], swiss_round(&ratings[..2], 2, &played));
+}
+
+#[test]
+fn test_run_tournament_ratings() {
+    let roster = roster();
+    for &pairing in &[Pairing::RoundRobin, Pairing::Swiss { rounds: 2 }] {
+        let settings = settings(2, pairing);
+        let result = run_tournament(&settings, &roster, &|_| {});
+        let rounds = if pairing == Pairing::RoundRobin { 1 } else { 2 };
+        // 2 seeds, 2 rotations each
+        assert_eq!(4 * rounds, result.matches.len());
+        // the seats are rotated with the same seed
+        assert_eq!(result.matches[0].job.seed, result.matches[1].job.seed);
+        assert_eq!(vec![0, 1], result.matches[0].job.seats);
+        assert_eq!(vec![1, 0], result.matches[1].job.seats);
+        let idle = &result.standings[0];
+        let killer = &result.standings[1];
+        assert_eq!(4 * rounds as u32, killer.matches);
+        assert_eq!(1.0, killer.win_rate());
+        assert!(killer.rating > INITIAL_RATING && idle.rating < INITIAL_RATING);
+        assert!((killer.rating + idle.rating - 2.0 * INITIAL_RATING).abs() < 1e-6);
+        // the same seed gives the same tournament
+        let again = run_tournament(&settings, &roster, &|_| {});
+        assert_eq!(result.standings, again.standings);
+        assert_eq!(result.matches.len(), again.matches.len());
+    }
+}
+
+#[test]
+fn test_write_tsv() {
+    let result = run_tournament(&settings(2, Pairing::RoundRobin), &roster(), &|_| {});
+    let mut buf = vec![];
+    write_matches_tsv(&mut buf, &result.matches, false).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!("it\titeration\touroboros_count\tbite_count\thead_to_head_count\tseed\tscore_a\tscore_b", lines[0]);
+    assert_eq!(5, lines.len());
+    assert!(lines[1].starts_with("000000\t"));
+    assert_eq!(8, lines[1].split('\t').count());
+    // the seats are appended only on demand
+    let mut buf = vec![];
+    write_matches_tsv(&mut buf, &result.matches, true).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].ends_with("\tscore_b\tseats"));
+    assert!(lines[1].ends_with("\t0,1") && lines[2].ends_with("\t1,0"));
+    assert_eq!(9, lines[1].split('\t').count());
+    let mut buf = vec![];
+    write_standings_tsv(&mut buf, &result.standings).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    assert!(text.lines().nth(2).unwrap().starts_with("killer\t4\t4.00\t1.0000\t"));
+}
+
+#[test]
+fn test_parallel_batch_is_deterministic() {
+    let roster = roster();
+    let mut settings = settings(2, Pairing::RoundRobin);
+    settings.matches = 8;
+    let sequential = run_tournament(&settings, &roster, &|_| {});
+    settings.workers = 4;
+    let parallel = run_tournament(&settings, &roster, &|_| {});
+    let stats = |r: &TournamentResult| r.matches.iter().map(|m| (m.job.clone(), m.stats.clone())).collect::<Vec<_>>();
+    // timings differ from run to run, everything else must be the same
+    assert_eq!(stats(&sequential), stats(&parallel));
+    assert_eq!(sequential.standings, parallel.standings);
+}
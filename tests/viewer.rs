@@ -0,0 +1,75 @@
+use xcg::model::*;
+use xcg::viewer::*;
+use console::Key;
+use std::time::Duration;
+
+fn make_replay() -> Replay {
+    // A goes around 2 cells and closes the loop on the 4th tick, B stays
+    let a = [Move::Right, Move::Down, Move::Right, Move::Up, Move::Right];
+    Replay {
+        height: 6,
+        width: 6,
+        duration: 20,
+        ratio: 0.9,
+        player_names: vec!["A".to_string(), "B".to_string()],
+        moves: a.iter().map(|&mv| vec![mv, Move::Stop]).collect(),
+        random_seed: Some(1),
+        rules: Rules::Sequential,
+        map: None,
+    }
+}
+
+#[test]
+fn test_viewer_navigation() {
+    let mut viewer = ViewerState::new(&make_replay(), Duration::from_millis(40));
+    assert_eq!((0, 10, 5), (viewer.pos, viewer.len(), viewer.ticks()));
+    assert_eq!(0, viewer.current().stats.iteration);
+    viewer.apply(Command::NextMove);
+    assert_eq!((1, 0), (viewer.pos, viewer.tick()));
+    viewer.apply(Command::NextTick);
+    assert_eq!((2, 1), (viewer.pos, viewer.tick()));
+    viewer.apply(Command::NextMove);
+    viewer.apply(Command::PrevTick);
+    assert_eq!(2, viewer.pos);
+    viewer.apply(Command::PrevTick);
+    viewer.apply(Command::PrevMove);
+    assert_eq!(0, viewer.pos);
+    // go to the tick 3 by the keys, too far is the end
+    for key in vec![Key::Char('3'), Key::Enter] {
+        assert!(viewer.on_key(key));
+    }
+    assert_eq!((6, 3), (viewer.pos, viewer.current().stats.iteration));
+    viewer.apply(Command::Jump(100));
+    assert_eq!(10, viewer.pos);
+    // the playing restarts from the beginning and stops at the end
+    viewer.on_key(Key::Char(' '));
+    assert_eq!((0, true), (viewer.pos, viewer.playing));
+    for _ in 0..10 {
+        viewer.advance();
+    }
+    assert_eq!((10, false), (viewer.pos, viewer.playing));
+    viewer.apply(Command::Faster);
+    assert_eq!(Duration::from_millis(20), viewer.delay);
+    assert!(!viewer.on_key(Key::Char('q')));
+}
+
+#[test]
+fn test_viewer_flooded() {
+    let mut viewer = ViewerState::new(&make_replay(), Duration::from_millis(40));
+    // A moves `order`-th in each tick, the frame after its move on the tick 3 is the capture
+    let order = viewer.current().reordering.iter().position(|&k| k == 0).unwrap();
+    let capture = 3 * 2 + order + 1;
+    while viewer.pos + 1 < capture {
+        viewer.apply(Command::NextMove);
+        assert!(viewer.flooded().is_empty());
+    }
+    viewer.apply(Command::NextMove);
+    assert_eq!(capture, viewer.pos);
+    assert_eq!(vec![Point(1, 1), Point(1, 2)], viewer.flooded());
+    let text = viewer.render(false);
+    assert!(text.contains("* + + . . *"));
+    assert!(text.contains(HELP));
+    // the cells are captured already, nothing new on the next step
+    viewer.apply(Command::Last);
+    assert!(viewer.flooded().is_empty());
+}
@@ -0,0 +1,57 @@
+use xcg::utils::Trim;
+use xcg::model::*;
+use xcg::bot::common::{distance_map, is_passable, voronoi};
+
+#[test]
+fn test_distance_map_around_holes() {
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. . .#. . .*.
+        *. . .#. . .*.
+        *. . .#. . .*.
+        *.*.*.*.*.*.*.
+    "#);
+    let field = &gs.field;
+    let dm = distance_map(field, &[Point(2, 1)], |p| is_passable(field, p));
+    assert_eq!(Some(0), dm.get(Point(2, 1)));
+    assert_eq!(Some(2), dm.get(Point(1, 2)));
+    // around the wall through the border
+    assert_eq!(Some(7), dm.get(Point(2, 4)));
+    assert_eq!(None, dm.get(Point(2, 3)));
+    assert_eq!(None, dm.get(Point(-1, 0)));
+    // only the empty cells, the area behind the wall is unreachable
+    let dm = distance_map(field, &[Point(2, 1)], |p| field.cells.get(p) == Cell::Empty);
+    assert_eq!(None, dm.get(Point(2, 4)));
+    // several sources
+    let dm = distance_map(field, &[Point(2, 1), Point(2, 5)], |p| is_passable(field, p));
+    assert_eq!(Some(1), dm.get(Point(2, 4)));
+}
+
+#[test]
+fn test_voronoi_partition() {
+    let gs = game_state(r#"
+        *.*.*.*.*.*.*.
+        *. A . . . .*.
+        *. . . . . .*.
+        *. . . . . B*.
+        *.*.*.*.*.*.*.
+    "#);
+    let field = &gs.field;
+    let heads: Vec<Option<Point>> = gs.players.iter().map(|p| p.head().cloned()).collect();
+    let v = voronoi(field, &heads, |p| is_passable(field, p));
+    assert_eq!(Some(0), v.owner(Point(1, 1)));
+    assert_eq!(Some(1), v.owner(Point(3, 5)));
+    // (1, 4) and (3, 2) are 3 moves away from both heads
+    assert!(v.is_contested(Point(1, 4)) && v.is_contested(Point(3, 2)));
+    assert_eq!(Some(3), v.distances.get(Point(1, 4)));
+    let sizes = v.sizes(2);
+    assert_eq!(sizes[0], sizes[1]);
+    let contested = (0..5).flat_map(|i| (0..7).map(move |j| Point(i, j)))
+        .filter(|&p| v.is_contested(p))
+        .count();
+    assert_eq!(35, sizes[0] + sizes[1] + contested);
+}
+
+fn game_state(gs: &str) -> GameState {
+    GameState::parse_string(&gs.trim_indent()).unwrap()
+}